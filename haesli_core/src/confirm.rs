@@ -0,0 +1,160 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use tokio::sync::mpsc;
+
+use crate::{connection::ChannelNum, methods::DeliveryTag};
+
+/// The publisher confirms of a channel, enabled using Confirm.Select.
+#[derive(Debug, Default)]
+pub struct Confirms {
+    last_tag: DeliveryTag,
+}
+
+impl Confirms {
+    /// Returns the delivery tag of the next published message. Published messages are counted
+    /// starting at 1, independent of the messages delivered on the channel.
+    pub fn next_tag(&mut self) -> DeliveryTag {
+        self.last_tag += 1;
+        self.last_tag
+    }
+}
+
+/// Whether a published message was handled, which the transport sends to the publisher as a
+/// Basic.Ack or Basic.Nack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfirmEvent {
+    pub channel: ChannelNum,
+    pub delivery_tag: DeliveryTag,
+    pub ack: bool,
+}
+
+// the publisher relies on getting a confirm for every message, so they can't be dropped when the
+// connection is busy
+pub type ConfirmSender = mpsc::UnboundedSender<ConfirmEvent>;
+pub type ConfirmReceiver = mpsc::UnboundedReceiver<ConfirmEvent>;
+
+/// A published message that is waiting to be confirmed to the publisher.
+///
+/// Every queue the message is routed to gets its own part of the confirm using [`split`], and
+/// calls [`confirm`] once it has enqueued the message. The publisher gets a Basic.Ack when all
+/// parts are confirmed, or a Basic.Nack if a part was dropped without being confirmed, for
+/// example because the queue was deleted in the meantime.
+///
+/// [`split`]: PendingConfirm::split
+/// [`confirm`]: PendingConfirm::confirm
+#[derive(Debug)]
+pub struct PendingConfirm {
+    state: Arc<ConfirmState>,
+    confirmed: bool,
+}
+
+#[derive(Debug)]
+struct ConfirmState {
+    delivery_tag: DeliveryTag,
+    channel: ChannelNum,
+    confirm_sender: ConfirmSender,
+    failed: AtomicBool,
+}
+
+impl PendingConfirm {
+    pub fn new(
+        delivery_tag: DeliveryTag,
+        channel: ChannelNum,
+        confirm_sender: ConfirmSender,
+    ) -> Self {
+        Self {
+            state: Arc::new(ConfirmState {
+                delivery_tag,
+                channel,
+                confirm_sender,
+                failed: AtomicBool::new(false),
+            }),
+            confirmed: false,
+        }
+    }
+
+    /// Returns another part of the confirm, which has to be confirmed as well.
+    pub fn split(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            confirmed: false,
+        }
+    }
+
+    pub fn confirm(mut self) {
+        self.confirmed = true;
+    }
+}
+
+impl Drop for PendingConfirm {
+    fn drop(&mut self) {
+        if !self.confirmed {
+            self.state.failed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for ConfirmState {
+    fn drop(&mut self) {
+        let confirm = ConfirmEvent {
+            channel: self.channel,
+            delivery_tag: self.delivery_tag,
+            ack: !*self.failed.get_mut(),
+        };
+
+        // nobody is waiting for the confirm if the connection is already closed
+        let _ = self.confirm_sender.send(confirm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::{ConfirmEvent, PendingConfirm};
+    use crate::connection::ChannelNum;
+
+    #[test]
+    fn ack_after_all_parts_are_confirmed() {
+        let (confirm_sender, mut confirm_receiver) = mpsc::unbounded_channel();
+        let confirm = PendingConfirm::new(3, ChannelNum::new(1), confirm_sender);
+        let queue_a = confirm.split();
+        let queue_b = confirm.split();
+
+        confirm.confirm();
+        queue_a.confirm();
+        assert!(confirm_receiver.try_recv().is_err());
+
+        queue_b.confirm();
+        assert_eq!(
+            confirm_receiver.try_recv(),
+            Ok(ConfirmEvent {
+                channel: ChannelNum::new(1),
+                delivery_tag: 3,
+                ack: true,
+            })
+        );
+    }
+
+    #[test]
+    fn nack_if_a_part_is_dropped() {
+        let (confirm_sender, mut confirm_receiver) = mpsc::unbounded_channel();
+        let confirm = PendingConfirm::new(1, ChannelNum::new(1), confirm_sender);
+        let queue = confirm.split();
+
+        drop(queue);
+        confirm.confirm();
+
+        assert_eq!(
+            confirm_receiver.try_recv(),
+            Ok(ConfirmEvent {
+                channel: ChannelNum::new(1),
+                delivery_tag: 1,
+                ack: false,
+            })
+        );
+    }
+}
@@ -10,9 +10,13 @@ use parking_lot::Mutex;
 use tokio::sync::mpsc;
 
 use crate::{
+    confirm::{ConfirmSender, Confirms},
     consumer::Consumer,
+    delivery::{self, Deliveries, Prefetch},
     methods::{self, Method},
-    newtype_id, GlobalData, Queue, SingleVec,
+    newtype_id,
+    transaction::Transaction,
+    GlobalData, Queue, SingleVec,
 };
 
 newtype_id!(pub ConnectionId);
@@ -57,8 +61,11 @@ pub struct ConnectionInner {
     pub peer_addr: SocketAddr,
     pub global_data: GlobalData,
     pub channels: Mutex<HashMap<ChannelNum, Channel>>,
-    pub exclusive_queues: Vec<Queue>,
+    /// The exclusive queues declared by the connection, which are deleted when it closes
+    pub exclusive_queues: Mutex<Vec<Queue>>,
     pub event_sender: ConEventSender,
+    /// The publisher confirms of all channels, which are sent by the transport
+    pub confirm_sender: ConfirmSender,
     pub consuming: Mutex<Vec<Consumer>>,
 }
 
@@ -79,14 +86,16 @@ impl ConnectionInner {
         peer_addr: SocketAddr,
         global_data: GlobalData,
         event_sender: ConEventSender,
+        confirm_sender: ConfirmSender,
     ) -> Connection {
         Arc::new(Self {
             id,
             peer_addr,
             global_data,
             channels: Mutex::default(),
-            exclusive_queues: vec![],
+            exclusive_queues: Mutex::default(),
             event_sender,
+            confirm_sender,
             consuming: Mutex::default(),
         })
     }
@@ -94,12 +103,18 @@ impl ConnectionInner {
     pub fn close(&self) {
         // todo: make a better system that prevents all leaks
 
-        let mut global_data = self.global_data.lock();
-        global_data.connections.remove(&self.id);
-        self.consuming
-            .lock()
+        {
+            let mut global_data = self.global_data.lock();
+            global_data.connections.remove(&self.id);
+        }
+
+        // the consumers have to be removed first, or requeued messages might be delivered to them again
+        std::mem::take(&mut *self.consuming.lock())
             .iter()
-            .for_each(|consumer| drop(consumer.queue.consumers.lock().remove(&consumer.id)));
+            .for_each(|consumer| drop(consumer.queue.remove_consumer(consumer.id)));
+
+        let channels = std::mem::take(&mut *self.channels.lock());
+        channels.values().for_each(|channel| channel.close());
     }
 }
 
@@ -112,6 +127,13 @@ pub struct ChannelInner {
     pub connection: Connection,
     pub global_data: GlobalData,
     pub event_sender: ConEventSender,
+    /// The messages that have been delivered on this channel and are waiting for an acknowledgement
+    pub deliveries: Mutex<Deliveries>,
+    pub prefetch: Mutex<Prefetch>,
+    /// The publisher confirms of the channel, `None` if the channel isn't in confirm mode
+    pub confirms: Mutex<Option<Confirms>>,
+    /// The current transaction of the channel, `None` if the channel isn't in transaction mode
+    pub transaction: Mutex<Option<Transaction>>,
 }
 
 impl ChannelInner {
@@ -129,12 +151,39 @@ impl ChannelInner {
             connection,
             global_data,
             event_sender: method_queue,
+            deliveries: Mutex::default(),
+            prefetch: Mutex::default(),
+            confirms: Mutex::default(),
+            transaction: Mutex::default(),
         })
     }
 
     pub fn close(&self) {
-        let mut global_data = self.global_data.lock();
-        global_data.channels.remove(&self.id);
+        {
+            let mut global_data = self.global_data.lock();
+            global_data.channels.remove(&self.id);
+        }
+
+        {
+            let mut channels = self.connection.channels.lock();
+            if channels.get(&self.num).map(|channel| channel.id) == Some(self.id) {
+                channels.remove(&self.num);
+            }
+        }
+
+        // the consumers have to be removed first, or requeued messages might be delivered to them again
+        self.connection.consuming.lock().retain(|consumer| {
+            if consumer.channel.id == self.id {
+                consumer.queue.remove_consumer(consumer.id);
+                false
+            } else {
+                true
+            }
+        });
+
+        // messages that haven't been acknowledged when the channel closes are redelivered
+        let unacked = self.deliveries.lock().take_all();
+        delivery::requeue(unacked);
     }
 }
 
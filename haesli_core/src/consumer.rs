@@ -10,4 +10,8 @@ pub struct Consumer {
     pub tag: String,
     pub channel: Channel,
     pub queue: Queue,
+    /// Whether messages are considered acknowledged as soon as they are delivered
+    pub no_ack: bool,
+    /// The maximum amount of unacked messages the consumer can have. Zero means no limit.
+    pub prefetch_count: u16,
 }
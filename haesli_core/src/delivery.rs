@@ -0,0 +1,162 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use crate::{
+    consumer::ConsumerId,
+    message::{Message, QueuedMessage},
+    methods::DeliveryTag,
+    queue::{Queue, QueueEvent},
+};
+
+/// A message that was delivered to a client, but has not been acknowledged yet.
+#[derive(Debug, Clone)]
+pub struct Unacked {
+    pub message: Message,
+    /// The queue the message was taken from. It is put back there if it gets requeued.
+    pub queue: Queue,
+    /// The consumer the message was delivered to, or `None` if it was fetched using Basic.Get.
+    pub consumer: Option<ConsumerId>,
+    /// The record of the message in the message log of the queue, if it is persistent
+    pub record: Option<u64>,
+}
+
+/// Puts messages back at the front of the queues they were taken from, marked as redelivered, and
+/// notifies the queues about it. The messages are expected to be ordered by their delivery tag.
+pub fn requeue(unacked: Vec<Unacked>) {
+    let mut queues = Vec::<Queue>::new();
+
+    // go from the back so that the message with the lowest delivery tag ends up in front
+    for unacked in unacked.into_iter().rev() {
+        if !queues
+            .iter()
+            .any(|queue| Arc::ptr_eq(queue, &unacked.queue))
+        {
+            queues.push(unacked.queue.clone());
+        }
+
+        unacked.queue.messages.prepend(QueuedMessage {
+            message: unacked.message,
+            redelivered: true,
+            record: unacked.record,
+        });
+    }
+
+    for queue in queues {
+        let _ = queue.event_send.send(QueueEvent::MessagesRequeued);
+    }
+}
+
+/// The prefetch limits of a channel, set using Basic.Qos. A limit of zero means no limit.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Prefetch {
+    /// The limit for consumers that are created on the channel from now on
+    pub consumer: u16,
+    /// The limit shared by all consumers on the channel
+    pub channel: u16,
+}
+
+/// Keeps track of the messages that were delivered on a channel.
+#[derive(Debug, Default)]
+pub struct Deliveries {
+    last_tag: DeliveryTag,
+    unacked: BTreeMap<DeliveryTag, Unacked>,
+    /// The amount of unacked messages of each consumer, for checking the prefetch limits
+    consumer_unacked: HashMap<ConsumerId, usize>,
+}
+
+impl Deliveries {
+    /// Returns a new delivery tag. Delivery tags are never zero and increase monotonically.
+    pub fn next_tag(&mut self) -> DeliveryTag {
+        self.last_tag += 1;
+        self.last_tag
+    }
+
+    pub fn track(&mut self, tag: DeliveryTag, unacked: Unacked) {
+        if let Some(consumer) = unacked.consumer {
+            *self.consumer_unacked.entry(consumer).or_default() += 1;
+        }
+        self.unacked.insert(tag, unacked);
+    }
+
+    /// Removes the messages referenced by a delivery tag from the unacked messages.
+    ///
+    /// If `multiple` is set, all messages up to and including the tag are removed, and a tag of
+    /// zero refers to all outstanding messages. Returns `None` if the tag doesn't reference an
+    /// unacked message.
+    pub fn remove(&mut self, tag: DeliveryTag, multiple: bool) -> Option<Vec<Unacked>> {
+        if multiple && tag == 0 {
+            return Some(self.take_all());
+        }
+
+        if !self.unacked.contains_key(&tag) {
+            return None;
+        }
+
+        let removed = if multiple {
+            let rest = self.unacked.split_off(&(tag + 1));
+            std::mem::replace(&mut self.unacked, rest)
+        } else {
+            self.unacked.remove_entry(&tag).into_iter().collect()
+        };
+
+        Some(self.untrack(removed))
+    }
+
+    /// Returns the delivery tags of the messages that [`remove`](Self::remove) would remove, without
+    /// removing them.
+    pub fn tags(&self, tag: DeliveryTag, multiple: bool) -> Option<Vec<DeliveryTag>> {
+        if multiple && tag == 0 {
+            return Some(self.unacked.keys().copied().collect());
+        }
+
+        if !self.unacked.contains_key(&tag) {
+            return None;
+        }
+
+        if multiple {
+            Some(self.unacked.range(..=tag).map(|(tag, _)| *tag).collect())
+        } else {
+            Some(vec![tag])
+        }
+    }
+
+    /// Removes the messages with the delivery tags from the unacked messages. Tags that don't
+    /// reference an unacked message are ignored.
+    pub fn remove_tags(&mut self, tags: &[DeliveryTag]) -> Vec<Unacked> {
+        let removed = tags
+            .iter()
+            .filter_map(|tag| self.unacked.remove_entry(tag))
+            .collect();
+
+        self.untrack(removed)
+    }
+
+    /// Removes all unacked messages, ordered by their delivery tag.
+    pub fn take_all(&mut self) -> Vec<Unacked> {
+        let removed = std::mem::take(&mut self.unacked);
+        self.untrack(removed)
+    }
+
+    fn untrack(&mut self, removed: BTreeMap<DeliveryTag, Unacked>) -> Vec<Unacked> {
+        for consumer in removed.values().filter_map(|unacked| unacked.consumer) {
+            if let Some(count) = self.consumer_unacked.get_mut(&consumer) {
+                *count -= 1;
+                if *count == 0 {
+                    self.consumer_unacked.remove(&consumer);
+                }
+            }
+        }
+
+        removed.into_values().collect()
+    }
+
+    pub fn unacked_len(&self) -> usize {
+        self.unacked.len()
+    }
+
+    pub fn consumer_unacked_len(&self, consumer: ConsumerId) -> usize {
+        self.consumer_unacked.get(&consumer).copied().unwrap_or(0)
+    }
+}
@@ -1,44 +1,62 @@
-use std::{
-    borrow::Borrow,
-    collections::HashMap,
-    fmt::{Display, Formatter},
-    sync::Arc,
-};
+use std::{borrow::Borrow, collections::HashMap, sync::Arc};
 
-use crate::{newtype, Queue};
+use haesli_datastructure::TopicTrie;
 
-#[derive(Debug)]
-pub enum TopicSegment {
-    Word(String),
-    SingleWildcard,
-    MultiWildcard,
+use crate::{methods::Table, newtype, Queue};
+
+/// How the headers of a headers exchange binding are matched against the message headers,
+/// set using the `x-match` binding argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadersMatch {
+    /// All headers of the binding have to match
+    All,
+    /// At least one header of the binding has to match
+    Any,
+}
+
+/// The binding of a queue or exchange to a headers exchange
+#[derive(Debug, PartialEq)]
+pub struct HeadersBinding {
+    pub x_match: HeadersMatch,
+    /// The headers that are matched against, without the `x-` arguments
+    pub headers: Table,
 }
 
-impl Display for TopicSegment {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Word(str) => str.fmt(f),
-            Self::SingleWildcard => f.write_str("*"),
-            Self::MultiWildcard => f.write_str("#"),
+/// Where an exchange routes the messages that match a binding
+#[derive(Debug, Clone)]
+pub enum Destination {
+    Queue(Queue),
+    /// An exchange bound using `Exchange.Bind`, which routes the message again with its own
+    /// bindings
+    Exchange(ExchangeName),
+}
+
+impl Destination {
+    /// Whether both destinations are the same queue or exchange
+    pub fn is_same(&self, other: &Destination) -> bool {
+        match (self, other) {
+            (Destination::Queue(a), Destination::Queue(b)) => Arc::ptr_eq(a, b),
+            (Destination::Exchange(a), Destination::Exchange(b)) => a == b,
+            _ => false,
         }
     }
 }
 
 #[derive(Debug)]
 pub enum ExchangeType {
-    /// Routes a message to a queue if the routing-keys are equal
-    Direct { bindings: HashMap<String, Queue> },
-    /// Always routes the message to a queue
-    Fanout { bindings: Vec<Queue> },
-    /// Routes a message to a queue if the routing key matches the pattern
-    Topic {
-        bindings: Vec<(Vec<TopicSegment>, Queue)>,
+    /// Routes a message to all destinations bound with a routing-key equal to the message's
+    Direct {
+        bindings: HashMap<String, Vec<Destination>>,
     },
+    /// Always routes the message to a destination
+    Fanout { bindings: Vec<Destination> },
+    /// Routes a message to a destination if the routing key matches the pattern
+    Topic { bindings: TopicTrie<Destination> },
     /// Is bound with a table of headers and values, and matches if the message headers
     /// match up with the binding headers
-    ///
-    /// Unsupported for now.
-    Headers,
+    Headers {
+        bindings: Vec<(HeadersBinding, Destination)>,
+    },
     /// The message is sent to the server system service with the name of the routing-key
     ///
     /// Unsupported for now.
@@ -62,6 +80,14 @@ pub struct Exchange {
     pub name: ExchangeName,
     pub kind: ExchangeType,
     pub durable: bool,
+    /// Whether the exchange is deleted once its last binding is removed
+    pub auto_delete: bool,
+}
+
+/// Whether the exchange with this name is declared by the server itself. These exchanges can't be
+/// deleted by clients.
+pub fn is_predeclared(name: &str) -> bool {
+    name.is_empty() || name.starts_with("amqp.")
 }
 
 pub fn default_exchanges() -> HashMap<ExchangeName, Exchange> {
@@ -74,6 +100,7 @@ pub fn default_exchanges() -> HashMap<ExchangeName, Exchange> {
             bindings: HashMap::new(),
         },
         durable: true,
+        auto_delete: false,
     };
 
     let direct_name = ExchangeName::new("amqp.direct".to_owned().into());
@@ -83,6 +110,7 @@ pub fn default_exchanges() -> HashMap<ExchangeName, Exchange> {
             bindings: HashMap::new(),
         },
         durable: true,
+        auto_delete: false,
     };
 
     let fanout_name = ExchangeName::new("amqp.fanout".to_owned().into());
@@ -92,23 +120,34 @@ pub fn default_exchanges() -> HashMap<ExchangeName, Exchange> {
             bindings: Vec::new(),
         },
         durable: true,
+        auto_delete: false,
     };
 
     let topic_name = ExchangeName::new("amqp.topic".to_owned().into());
     let topic = Exchange {
         name: topic_name.clone(),
         kind: ExchangeType::Topic {
-            bindings: Vec::new(),
+            bindings: TopicTrie::new(),
         },
         durable: true,
+        auto_delete: false,
     };
 
-    // we don't implement headers (yet), so don't provide the default exchange for it
+    let headers_name = ExchangeName::new("amqp.headers".to_owned().into());
+    let headers = Exchange {
+        name: headers_name.clone(),
+        kind: ExchangeType::Headers {
+            bindings: Vec::new(),
+        },
+        durable: true,
+        auto_delete: false,
+    };
 
     HashMap::from([
         (empty_name, empty),
         (direct_name, direct),
         (fanout_name, fanout),
         (topic_name, topic),
+        (headers_name, headers),
     ])
 }
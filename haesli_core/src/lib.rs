@@ -1,17 +1,22 @@
 #![warn(rust_2018_idioms)]
 
+pub mod confirm;
 pub mod connection;
 pub mod consumer;
+pub mod delivery;
 pub mod error;
 pub mod exchange;
 mod macros;
 pub mod message;
 pub mod methods;
 pub mod queue;
+pub mod transaction;
 
 use std::{
     collections::HashMap,
     fmt::{Debug, Formatter},
+    future::Future,
+    pin::Pin,
     sync::Arc,
 };
 
@@ -27,6 +32,10 @@ use crate::{
 
 pub type SingleVec<T> = smallvec::SmallVec<[T; 1]>;
 
+/// The future returned by the handlers that the transport calls into. The transport doesn't read
+/// more frames of the connection until it's done.
+pub type HandlerFuture<T> = Pin<Box<dyn Future<Output = Result<T, error::ProtocolError>> + Send>>;
+
 #[derive(Clone)]
 // todo: what if this was downstream?
 pub struct GlobalData {
@@ -6,6 +6,16 @@ use crate::{connection::ContentHeader, newtype_id, SingleVec};
 
 pub type Message = Arc<MessageInner>;
 
+/// A message stored in a queue, together with the delivery state that is specific to that queue.
+#[derive(Debug, Clone)]
+pub struct QueuedMessage {
+    pub message: Message,
+    /// Whether the message has already been delivered from this queue before
+    pub redelivered: bool,
+    /// The record of the message in the message log of the queue, if it is persistent
+    pub record: Option<u64>,
+}
+
 newtype_id!(pub MessageId);
 
 #[derive(Debug)]
@@ -146,6 +146,10 @@ pub enum Method {
     ExchangeDeclareOk(ExchangeDeclareOk),
     ExchangeDelete(ExchangeDelete),
     ExchangeDeleteOk(ExchangeDeleteOk),
+    ExchangeBind(ExchangeBind),
+    ExchangeBindOk(ExchangeBindOk),
+    ExchangeUnbind(ExchangeUnbind),
+    ExchangeUnbindOk(ExchangeUnbindOk),
     QueueDeclare(QueueDeclare),
     QueueDeclareOk(QueueDeclareOk),
     QueueBind(QueueBind),
@@ -173,6 +177,9 @@ pub enum Method {
     BasicRecoverAsync(BasicRecoverAsync),
     BasicRecover(BasicRecover),
     BasicRecoverOk(BasicRecoverOk),
+    BasicNack(BasicNack),
+    ConfirmSelect(ConfirmSelect),
+    ConfirmSelectOk(ConfirmSelectOk),
     TxSelect(TxSelect),
     TxSelectOk(TxSelectOk),
     TxCommit(TxCommit),
@@ -429,7 +436,8 @@ pub struct ExchangeDeclare {
     /// Durable exchanges remain active when a server restarts. Non-durable exchanges
     /// (transient exchanges) are purged if/when a server restarts.
     pub durable: Bit,
-    pub reserved_2: Bit,
+    /// If set, the exchange is deleted when all queues have finished using it.
+    pub auto_delete: Bit,
     pub reserved_3: Bit,
     pub no_wait: NoWait,
     /// A set of arguments for the declaration. The syntax and semantics of these
@@ -466,6 +474,57 @@ pub struct ExchangeDelete {
 #[derive(Debug, Clone, PartialEq)]
 pub struct ExchangeDeleteOk;
 
+/// Exchanges match and distribute messages across queues. Exchanges can be configured in
+/// the server or declared at runtime.
+/// This method binds an exchange to an exchange. Messages that are routed to the
+/// destination exchange by the source exchange are routed again by the destination
+/// exchange, using its own bindings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeBind {
+    pub reserved_1: Short,
+    /// Specifies the name of the destination exchange to bind.
+    pub destination: ExchangeName,
+    /// Specifies the name of the source exchange to bind.
+    pub source: ExchangeName,
+    /// Specifies the routing key for the binding. The routing key is used for routing
+    /// messages depending on the exchange configuration. Not all exchanges use a
+    /// routing key - refer to the specific exchange documentation.
+    pub routing_key: Shortstr,
+    pub no_wait: NoWait,
+    /// A set of arguments for the binding. The syntax and semantics of these arguments
+    /// depends on the exchange class.
+    pub arguments: Table,
+}
+
+/// Exchanges match and distribute messages across queues. Exchanges can be configured in
+/// the server or declared at runtime.
+/// This method confirms that the bind was successful.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeBindOk;
+
+/// Exchanges match and distribute messages across queues. Exchanges can be configured in
+/// the server or declared at runtime.
+/// This method unbinds an exchange from an exchange.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeUnbind {
+    pub reserved_1: Short,
+    /// Specifies the name of the destination exchange to unbind.
+    pub destination: ExchangeName,
+    /// Specifies the name of the source exchange to unbind.
+    pub source: ExchangeName,
+    /// Specifies the routing key of the binding to unbind.
+    pub routing_key: Shortstr,
+    pub no_wait: NoWait,
+    /// Specifies the arguments of the binding to unbind.
+    pub arguments: Table,
+}
+
+/// Exchanges match and distribute messages across queues. Exchanges can be configured in
+/// the server or declared at runtime.
+/// This method confirms that the unbind was successful.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeUnbindOk;
+
 /// Queues store and forward messages. Queues can be configured in the server or created at
 /// runtime. Queues must be attached to at least one exchange in order to receive messages
 /// from publishers.
@@ -868,6 +927,61 @@ pub struct BasicRecover {
 #[derive(Debug, Clone, PartialEq)]
 pub struct BasicRecoverOk;
 
+/// The Basic class provides methods that support an industry-standard messaging model.
+/// This method allows a client to reject one or more incoming messages. It can be
+/// used to interrupt and cancel large incoming messages, or return untreatable
+/// messages to their original queue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicNack {
+    pub delivery_tag: DeliveryTag,
+    /// If set to 1, the delivery tag is treated as "up to and including", so that multiple
+    /// messages can be rejected with a single method. If set to zero, the delivery tag
+    /// refers to a single message. If the multiple field is 1, and the delivery tag is zero,
+    /// this indicates rejection of all outstanding messages.
+    pub multiple: Bit,
+    /// If requeue is true, the server will attempt to requeue the message.  If requeue
+    /// is false or the requeue  attempt fails the messages are discarded or dead-lettered.
+    pub requeue: Bit,
+}
+
+/// The Confirm class allows publishers to put the channel in confirm mode and
+/// subsequently be notified when messages have been handled by the broker. The
+/// intention is that all messages published on a channel in confirm mode will be
+/// acknowledged at some point. By acknowledging a message the broker assumes
+/// responsibility for it and indicates that it has done something it deems reasonable
+/// with it.
+/// Unroutable mandatory or immediate messages are acknowledged right after the
+/// Basic.Return method. Messages are acknowledged when all queues to which the message
+/// has been routed have either delivered the message and received an acknowledgement
+/// (if required), or enqueued the message (and persisted it if required).
+/// Published messages are assigned ascending sequence numbers, starting at 1 with the
+/// first Confirm.Select method. The server confirms messages by sending Basic.Ack
+/// methods referring to these sequence numbers.
+/// This method sets the channel to use publisher acknowledgements. The client can
+/// only use this method on a non-transactional channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmSelect {
+    pub no_wait: NoWait,
+}
+
+/// The Confirm class allows publishers to put the channel in confirm mode and
+/// subsequently be notified when messages have been handled by the broker. The
+/// intention is that all messages published on a channel in confirm mode will be
+/// acknowledged at some point. By acknowledging a message the broker assumes
+/// responsibility for it and indicates that it has done something it deems reasonable
+/// with it.
+/// Unroutable mandatory or immediate messages are acknowledged right after the
+/// Basic.Return method. Messages are acknowledged when all queues to which the message
+/// has been routed have either delivered the message and received an acknowledgement
+/// (if required), or enqueued the message (and persisted it if required).
+/// Published messages are assigned ascending sequence numbers, starting at 1 with the
+/// first Confirm.Select method. The server confirms messages by sending Basic.Ack
+/// methods referring to these sequence numbers.
+/// This method confirms to the client that the channel was successfully set to use
+/// publisher acknowledgements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmSelectOk;
+
 /// The Tx class allows publish and ack operations to be batched into atomic
 /// units of work.  The intention is that all publish and ack requests issued
 /// within a transaction will complete successfully or none of them will.
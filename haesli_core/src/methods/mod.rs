@@ -3,12 +3,13 @@ mod generated;
 use std::collections::HashMap;
 
 pub use generated::*;
+use serde::{Deserialize, Serialize};
 
 pub type TableFieldName = String;
 
 pub type Table = HashMap<TableFieldName, FieldValue>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FieldValue {
     Boolean(bool),
     ShortShortInt(i8),
@@ -1,29 +1,50 @@
-use std::{
-    borrow::Borrow,
-    collections::HashMap,
-    fmt::Debug,
-    sync::{atomic::AtomicUsize, Arc},
-};
+use std::{borrow::Borrow, collections::BTreeMap, fmt::Debug, sync::Arc};
 
 use parking_lot::Mutex;
 use tokio::sync::mpsc;
 
 use crate::{
+    confirm::PendingConfirm,
+    connection::ConnectionId,
     consumer::{Consumer, ConsumerId},
-    message::Message,
-    newtype, newtype_id, ChannelId,
+    message::{Message, QueuedMessage},
+    newtype, newtype_id,
 };
 
 pub type Queue = Arc<QueueInner>;
 
 #[derive(Debug)]
 pub enum QueueEvent {
-    PublishMessage(Message),
+    /// Messages have been put back into the queue, for example because they were rejected
+    MessagesRequeued,
+    /// A consumer of the queue might be able to take more messages
+    CapacityAvailable,
+    /// A new consumer started consuming from the queue
+    ConsumerAdded,
+    /// A consumer stopped consuming from the queue, which might have been the last one
+    ConsumerRemoved,
     Shutdown,
 }
 
-pub type QueueEventSender = mpsc::Sender<QueueEvent>;
-pub type QueueEventReceiver = mpsc::Receiver<QueueEvent>;
+// the events are small and must not be lost, so only the published messages are limited
+pub type QueueEventSender = mpsc::UnboundedSender<QueueEvent>;
+pub type QueueEventReceiver = mpsc::UnboundedReceiver<QueueEvent>;
+
+/// A message that was routed to the queue. If the channel it was published on is in confirm mode,
+/// the confirm has to be confirmed once the message is enqueued.
+#[derive(Debug)]
+pub struct QueuePublish {
+    pub message: Message,
+    pub confirm: Option<PendingConfirm>,
+}
+
+/// How many published messages can wait for the queue worker. Publishing to a queue that is full
+/// waits until there is room again, so publishers can't send messages faster than the queue takes
+/// them.
+pub const PUBLISH_CAPACITY: usize = 1024;
+
+pub type QueuePublishSender = mpsc::Sender<QueuePublish>;
+pub type QueuePublishReceiver = mpsc::Receiver<QueuePublish>;
 
 newtype_id!(pub QueueId);
 
@@ -45,22 +66,57 @@ pub struct QueueInner {
     pub id: QueueId,
     /// The visible name of the queue
     pub name: QueueName,
-    pub messages: haesli_datastructure::MessageQueue<Message>,
+    pub messages: haesli_datastructure::MessageQueue<QueuedMessage>,
     /// Whether the queue should be kept when the server restarts
     pub durable: bool,
-    /// To which connection the queue belongs to it will be deleted when the connection closes
-    // todo: connection or channel?
-    pub exclusive: Option<ChannelId>,
+    /// The connection the queue belongs to. It will be deleted when the connection closes, and other
+    /// connections can't use it.
+    pub exclusive: Option<ConnectionId>,
     /// Whether the queue will automatically be deleted when no consumers uses it anymore.
     /// The queue can always be manually deleted.
-    /// If auto-delete is enabled, it keeps track of the consumer count.
     pub deletion: QueueDeletion,
-    pub consumers: Mutex<HashMap<ConsumerId, Consumer>>,
+    /// The consumers of the queue. Ordered by their ID, which is the order messages are distributed in.
+    pub consumers: Mutex<BTreeMap<ConsumerId, Consumer>>,
     pub event_send: QueueEventSender,
+    pub publish_send: QueuePublishSender,
+}
+
+impl QueueInner {
+    /// Removes a consumer of the queue and notifies the queue, which deletes itself if it's an
+    /// auto-delete queue and this was the last consumer.
+    pub fn remove_consumer(&self, consumer_id: ConsumerId) -> Option<Consumer> {
+        let consumer = self.consumers.lock().remove(&consumer_id);
+
+        if consumer.is_some() {
+            let _ = self.event_send.send(QueueEvent::ConsumerRemoved);
+        }
+
+        consumer
+    }
+
+    /// Removes all consumers of the queue, for example because the queue is being deleted. Returns
+    /// them, so that their clients can be told using a Basic.Cancel sent by the server.
+    pub fn cancel_consumers(&self) -> Vec<Consumer> {
+        // the lock can't be held while locking `consuming`, the connection locks them the other way around
+        let consumers = std::mem::take(&mut *self.consumers.lock());
+
+        for consumer in consumers.values() {
+            consumer
+                .channel
+                .connection
+                .consuming
+                .lock()
+                .retain(|other| other.id != consumer.id);
+        }
+
+        consumers.into_values().collect()
+    }
 }
 
 #[derive(Debug)]
 pub enum QueueDeletion {
-    Auto(AtomicUsize),
+    /// The queue is deleted once its last consumer is cancelled. A queue that never had a consumer
+    /// is kept.
+    Auto,
     Manual,
 }
@@ -0,0 +1,33 @@
+use std::collections::HashSet;
+
+use crate::{message::Message, methods::DeliveryTag};
+
+/// What happens to delivered messages when a transaction is committed
+#[derive(Debug, Clone, Copy)]
+pub enum Settlement {
+    Ack,
+    Reject { requeue: bool },
+}
+
+/// The publishes and acknowledgements of a channel in transaction mode, enabled using Tx.Select.
+/// They only take effect when the transaction is committed, and are discarded if it is rolled back.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    /// The published messages, which haven't been routed yet
+    pub publishes: Vec<Message>,
+    /// The delivery tags of the acknowledged or rejected messages, in the order they were settled
+    pub settlements: Vec<(Vec<DeliveryTag>, Settlement)>,
+    settled_tags: HashSet<DeliveryTag>,
+}
+
+impl Transaction {
+    /// Whether the message with the delivery tag has already been settled in this transaction
+    pub fn is_settled(&self, tag: DeliveryTag) -> bool {
+        self.settled_tags.contains(&tag)
+    }
+
+    pub fn settle(&mut self, tags: Vec<DeliveryTag>, settlement: Settlement) {
+        self.settled_tags.extend(&tags);
+        self.settlements.push((tags, settlement));
+    }
+}
@@ -113,7 +113,7 @@ const EntityGraph = ({ data }: Props) => {
   const bindingEdges = data.exchanges
     .flatMap((e) => e.bindings.map((b) => [b, e] as const))
     .map(([b, e]) => ({
-      source: b.queue,
+      source: b.destination,
       target: e.name,
       label_to: `'${b.routingKey}'`,
       type: 'emptyEdge',
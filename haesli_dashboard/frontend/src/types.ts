@@ -23,7 +23,7 @@ export type Queue = {
 };
 
 export type Binding = {
-  queue: string;
+  destination: string;
   routingKey: string;
 };
 
@@ -8,7 +8,10 @@ use axum::{
     routing::{get, get_service},
     Json, Router,
 };
-use haesli_core::{exchange::ExchangeType, GlobalData};
+use haesli_core::{
+    exchange::{Destination, ExchangeType},
+    GlobalData,
+};
 use serde::Serialize;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{error, info};
@@ -98,7 +101,8 @@ struct Exchange {
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct Binding {
-    queue: String,
+    /// The name of the bound queue or exchange
+    destination: String,
     routing_key: String,
 }
 
@@ -161,31 +165,43 @@ fn map_exchange(exch: &haesli_core::exchange::Exchange) -> Exchange {
         bindings: match &exch.kind {
             ExchangeType::Direct { bindings } => bindings
                 .iter()
-                .map(|(name, _)| Binding {
-                    queue: name.clone(),
-                    routing_key: name.clone(),
+                .flat_map(|(routing_key, destinations)| {
+                    destinations.iter().map(|d| Binding {
+                        destination: destination_name(d),
+                        routing_key: routing_key.clone(),
+                    })
                 })
                 .collect(),
             ExchangeType::Fanout { bindings } => bindings
                 .iter()
-                .map(|q| Binding {
-                    queue: q.name.to_string(),
+                .map(|d| Binding {
+                    destination: destination_name(d),
                     routing_key: "".to_owned(),
                 })
                 .collect(),
             ExchangeType::Topic { bindings } => bindings
+                .bindings()
+                .into_iter()
+                .map(|(pattern, d)| Binding {
+                    destination: destination_name(d),
+                    routing_key: pattern,
+                })
+                .collect(),
+            ExchangeType::Headers { bindings } => bindings
                 .iter()
-                .map(|(segs, q)| Binding {
-                    queue: q.name.to_string(),
-                    routing_key: segs
-                        .iter()
-                        .map(|seg| seg.to_string())
-                        .collect::<Vec<_>>()
-                        .join("."),
+                .map(|(_, d)| Binding {
+                    destination: destination_name(d),
+                    routing_key: "".to_owned(),
                 })
                 .collect(),
-            ExchangeType::Headers => Vec::new(),
             ExchangeType::System => Vec::new(),
         },
     }
 }
+
+fn destination_name(destination: &Destination) -> String {
+    match destination {
+        Destination::Queue(queue) => queue.name.to_string(),
+        Destination::Exchange(exchange) => exchange.to_string(),
+    }
+}
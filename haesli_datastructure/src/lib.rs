@@ -1,3 +1,5 @@
 mod message_queue;
+mod topic_trie;
 
 pub use message_queue::MessageQueue;
+pub use topic_trie::TopicTrie;
@@ -30,11 +30,23 @@ impl<T> MessageQueue<T> {
         lock.push_back(message);
     }
 
+    /// Puts a message at the front of the queue, making it the next message to be taken out.
+    pub fn prepend(&self, message: T) {
+        let mut lock = self.deque.lock().unwrap();
+        lock.push_front(message);
+    }
+
     pub fn try_get(&self) -> Option<T> {
         let mut lock = self.deque.lock().unwrap();
         lock.pop_front()
     }
 
+    /// Removes all messages from the queue and returns them.
+    pub fn purge(&self) -> Vec<T> {
+        let mut lock = self.deque.lock().unwrap();
+        lock.drain(..).collect()
+    }
+
     pub fn len(&self) -> usize {
         self.deque.lock().unwrap().len()
     }
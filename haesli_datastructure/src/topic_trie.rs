@@ -0,0 +1,454 @@
+use std::collections::{HashMap, HashSet};
+
+/// The bindings of a topic exchange, indexed by the segments of their pattern.
+///
+/// Each node of the trie is a segment of a pattern, with `*` and `#` stored in their own children.
+/// Routing a message only walks the nodes that can match the routing key, so the cost depends on
+/// the length of the key instead of the amount of bindings.
+#[derive(Debug)]
+pub struct TopicTrie<T> {
+    root: Node<T>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    words: HashMap<String, Node<T>>,
+    /// The child for `*`, which matches exactly one word
+    single_wildcard: Option<Box<Node<T>>>,
+    /// The child for `#`, which matches zero or more words
+    multi_wildcard: Option<Box<Node<T>>>,
+    /// The values of the patterns that end at this node
+    values: Vec<T>,
+}
+
+impl<T> TopicTrie<T> {
+    pub fn new() -> Self {
+        Self {
+            root: Node::default(),
+        }
+    }
+
+    /// Returns the values bound with the pattern, creating an empty entry if there are none.
+    pub fn entry(&mut self, pattern: &str) -> &mut Vec<T> {
+        let mut node = &mut self.root;
+        for segment in pattern.split('.') {
+            node = node.child_or_insert(segment);
+        }
+        &mut node.values
+    }
+
+    /// Retains only the values bound with the pattern for which the predicate returns `true`.
+    /// Values bound with other patterns are not affected.
+    pub fn retain_pattern(&mut self, pattern: &str, mut f: impl FnMut(&T) -> bool) {
+        let segments = pattern.split('.').collect::<Vec<_>>();
+        self.root.retain_pattern(&segments, &mut f);
+    }
+
+    /// Retains only the values for which the predicate returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        self.root.retain(&mut f);
+    }
+
+    /// Returns the values of all patterns that match the routing key. Each matching pattern is only
+    /// included once, even if it matches the key in multiple ways.
+    pub fn match_key(&self, routing_key: &str) -> Vec<&T> {
+        let key = routing_key.split('.').collect::<Vec<_>>();
+        // only needed below a `#`, most keys never get there
+        let mut visited = None;
+        let mut matches = Vec::new();
+
+        self.root.match_key(&key, 0, &mut visited, &mut matches);
+
+        matches
+    }
+
+    /// Returns all values together with the pattern they are bound with.
+    pub fn bindings(&self) -> Vec<(String, &T)> {
+        let mut bindings = Vec::new();
+        self.root.collect_bindings(&mut Vec::new(), &mut bindings);
+        bindings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_empty()
+    }
+}
+
+impl<T> Default for TopicTrie<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Node<T> {
+    fn child_or_insert(&mut self, segment: &str) -> &mut Node<T> {
+        match segment {
+            "*" => self.single_wildcard.get_or_insert_with(Box::default),
+            "#" => self.multi_wildcard.get_or_insert_with(Box::default),
+            word => self.words.entry(word.to_owned()).or_default(),
+        }
+    }
+
+    fn child_mut(&mut self, segment: &str) -> Option<&mut Node<T>> {
+        match segment {
+            "*" => self.single_wildcard.as_deref_mut(),
+            "#" => self.multi_wildcard.as_deref_mut(),
+            word => self.words.get_mut(word),
+        }
+    }
+
+    fn remove_child_if_empty(&mut self, segment: &str) {
+        match segment {
+            "*" => {
+                if self.single_wildcard.as_ref().is_some_and(|c| c.is_empty()) {
+                    self.single_wildcard = None;
+                }
+            }
+            "#" => {
+                if self.multi_wildcard.as_ref().is_some_and(|c| c.is_empty()) {
+                    self.multi_wildcard = None;
+                }
+            }
+            word => {
+                if self.words.get(word).is_some_and(Node::is_empty) {
+                    self.words.remove(word);
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.is_empty()
+            && self.words.is_empty()
+            && self.single_wildcard.is_none()
+            && self.multi_wildcard.is_none()
+    }
+
+    fn retain_pattern(&mut self, segments: &[&str], f: &mut impl FnMut(&T) -> bool) {
+        match segments.split_first() {
+            None => self.values.retain(f),
+            Some((segment, rest)) => {
+                if let Some(child) = self.child_mut(segment) {
+                    child.retain_pattern(rest, f);
+                    self.remove_child_if_empty(segment);
+                }
+            }
+        }
+    }
+
+    fn retain(&mut self, f: &mut impl FnMut(&T) -> bool) {
+        self.values.retain(&mut *f);
+
+        self.words.retain(|_, child| {
+            child.retain(f);
+            !child.is_empty()
+        });
+
+        for wildcard in [&mut self.single_wildcard, &mut self.multi_wildcard] {
+            if let Some(child) = wildcard {
+                child.retain(f);
+                if child.is_empty() {
+                    *wildcard = None;
+                }
+            }
+        }
+    }
+
+    fn match_key<'a>(
+        &'a self,
+        key: &[&str],
+        idx: usize,
+        visited: &mut Option<HashSet<(*const Node<T>, usize)>>,
+        matches: &mut Vec<&'a T>,
+    ) {
+        // below a `#`, the same node can be reached for the same word in multiple ways.
+        // it only has to be looked at once, which also avoids matching a pattern multiple times.
+        // other nodes can only be reached in one way, so they don't have to be tracked
+        if let Some(visited) = visited {
+            if !visited.insert((self, idx)) {
+                return;
+            }
+        }
+
+        if let Some(child) = &self.multi_wildcard {
+            visited.get_or_insert_with(HashSet::new);
+            for idx in idx..=key.len() {
+                child.match_key(key, idx, visited, matches);
+            }
+        }
+
+        match key.get(idx) {
+            None => matches.extend(&self.values),
+            Some(word) => {
+                if let Some(child) = self.words.get(*word) {
+                    child.match_key(key, idx + 1, visited, matches);
+                }
+                if let Some(child) = &self.single_wildcard {
+                    child.match_key(key, idx + 1, visited, matches);
+                }
+            }
+        }
+    }
+
+    fn collect_bindings<'a>(
+        &'a self,
+        pattern: &mut Vec<&'a str>,
+        bindings: &mut Vec<(String, &'a T)>,
+    ) {
+        bindings.extend(self.values.iter().map(|value| (pattern.join("."), value)));
+
+        let wildcards = [("*", &self.single_wildcard), ("#", &self.multi_wildcard)];
+        let children = self
+            .words
+            .iter()
+            .map(|(word, child)| (word.as_str(), child))
+            .chain(
+                wildcards
+                    .into_iter()
+                    .filter_map(|(segment, child)| Some((segment, child.as_deref()?))),
+            );
+
+        for (segment, child) in children {
+            pattern.push(segment);
+            child.collect_bindings(pattern, bindings);
+            pattern.pop();
+        }
+    }
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self {
+            words: HashMap::new(),
+            single_wildcard: None,
+            multi_wildcard: None,
+            values: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::TopicTrie;
+
+    /// Whether the words of a routing key match the segments of a topic pattern. `*` matches exactly
+    /// one word, `#` matches zero or more words. The trie has to give the same results.
+    ///
+    /// This works like glob matching: when the rest of the key doesn't match, we go back to the last
+    /// `#` and let it match one more word.
+    fn topic_matches(pattern: &[&str], key: &[&str]) -> bool {
+        let mut pat_idx = 0;
+        let mut key_idx = 0;
+        // the position of the last `#` in the pattern and the first key word it hasn't matched yet
+        let mut backtrack = None;
+
+        while key_idx < key.len() {
+            match pattern.get(pat_idx) {
+                Some(&"*") => {
+                    pat_idx += 1;
+                    key_idx += 1;
+                }
+                Some(&"#") => {
+                    // first try to match zero words
+                    backtrack = Some((pat_idx, key_idx));
+                    pat_idx += 1;
+                }
+                Some(word) if *word == key[key_idx] => {
+                    pat_idx += 1;
+                    key_idx += 1;
+                }
+                _ => match backtrack {
+                    Some((multi_idx, multi_key_idx)) => {
+                        backtrack = Some((multi_idx, multi_key_idx + 1));
+                        pat_idx = multi_idx + 1;
+                        key_idx = multi_key_idx + 1;
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        // the key is used up, the rest of the pattern can only match if it's `#` matching zero words
+        pattern[pat_idx..].iter().all(|segment| *segment == "#")
+    }
+
+    fn matches(patterns: &[&str], routing_key: &str) -> Vec<usize> {
+        let mut trie = TopicTrie::new();
+        for (idx, pattern) in patterns.iter().enumerate() {
+            trie.entry(pattern).push(idx);
+        }
+
+        let mut matched = trie
+            .match_key(routing_key)
+            .into_iter()
+            .copied()
+            .collect::<Vec<_>>();
+        matched.sort_unstable();
+        matched
+    }
+
+    macro_rules! match_topics_test {
+        ($name:ident {
+            patterns: $($pattern:expr),*;
+            routing_key: $routing_key:expr;
+            expected: $($expected:expr),*;
+        }) => {
+            #[test]
+            fn $name() {
+                let matched = matches(&[$($pattern),*], $routing_key);
+                let expected: Vec<usize> = vec![$($expected),*];
+
+                assert_eq!(matched, expected);
+            }
+        };
+    }
+
+    match_topics_test!(match_spec_example_1 {
+        patterns: "*.stock.#";
+        routing_key: "usd.stock";
+        expected: 0;
+    });
+
+    match_topics_test!(match_spec_example_2 {
+        patterns: "*.stock.#";
+        routing_key: "eur.stock.db";
+        expected: 0;
+    });
+
+    match_topics_test!(match_spec_example_3 {
+        patterns: "*.stock.#";
+        routing_key: "stock.nasdaq";
+        expected: ;
+    });
+
+    match_topics_test!(match_no_wildcards {
+        patterns: "na.stock.usd", "sa.stock.peso", "stock.nasdaq", "usd.stock.na";
+        routing_key: "na.stock.usd";
+        expected: 0;
+    });
+
+    match_topics_test!(match_cursed_wildcards {
+        patterns: "*.*.*", "#.usd", "#.stock.*", "*.#", "#", "na.*";
+        routing_key: "na.stock.usd";
+        expected: 0, 1, 2, 3, 4;
+    });
+
+    match_topics_test!(match_empty_topic {
+        patterns: "", "bad";
+        routing_key: "";
+        expected: 0;
+    });
+
+    match_topics_test!(match_multi_wildcard_backtracking {
+        patterns: "#.a.b", "#.a", "a.#.a.#", "#.x.#.b";
+        routing_key: "a.x.a.b";
+        expected: 0, 2, 3;
+    });
+
+    match_topics_test!(match_multi_wildcard_zero_words {
+        patterns: "a.#", "#.a", "#.#.a.#", "a.#.#", "#";
+        routing_key: "a";
+        expected: 0, 1, 2, 3, 4;
+    });
+
+    match_topics_test!(match_adjacent_wildcards {
+        patterns: "#.#", "#.*", "*.#", "*.*.#", "#.*.*.*.*";
+        routing_key: "a.b.c";
+        expected: 0, 1, 2, 3;
+    });
+
+    match_topics_test!(match_same_pattern_twice {
+        patterns: "a.#", "a.#";
+        routing_key: "a.b";
+        expected: 0, 1;
+    });
+
+    #[test]
+    fn retain_pattern_removes_only_that_pattern() {
+        let mut trie = TopicTrie::new();
+        trie.entry("a.*").extend([1, 2]);
+        trie.entry("a.#").push(1);
+
+        trie.retain_pattern("a.*", |value| *value != 1);
+        assert_eq!(trie.match_key("a.b"), [&1, &2]);
+
+        trie.retain_pattern("a.*", |_| false);
+        trie.retain_pattern("a.b", |_| false);
+        assert_eq!(trie.match_key("a.b"), [&1]);
+
+        trie.retain(|_| false);
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn bindings_contain_patterns() {
+        let mut trie = TopicTrie::new();
+        trie.entry("a.*.#").push(1);
+        trie.entry("b").push(2);
+
+        let mut bindings = trie.bindings();
+        bindings.sort();
+
+        assert_eq!(bindings, [("a.*.#".to_owned(), &1), ("b".to_owned(), &2)]);
+    }
+
+    /// A simple recursive implementation of the topic matching rules, which is obviously correct
+    /// but has exponential runtime
+    fn reference_matches(pattern: &[&str], key: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => key.is_empty(),
+            Some((&"#", rest)) => {
+                (0..=key.len()).any(|skipped| reference_matches(rest, &key[skipped..]))
+            }
+            Some((&"*", rest)) => !key.is_empty() && reference_matches(rest, &key[1..]),
+            Some((word, rest)) => key.first() == Some(word) && reference_matches(rest, &key[1..]),
+        }
+    }
+
+    fn pattern_strategy() -> impl Strategy<Value = Vec<&'static str>> {
+        // a small alphabet, so that words actually match sometimes
+        prop::collection::vec(prop::sample::select(&["a", "b", "c", "*", "#"][..]), 1..8)
+    }
+
+    proptest! {
+        #[test]
+        fn topic_matches_like_reference(
+            pattern in pattern_strategy(),
+            key in prop::collection::vec(prop::sample::select(&["a", "b", "c"][..]), 1..10),
+        ) {
+            prop_assert_eq!(
+                topic_matches(&pattern, &key),
+                reference_matches(&pattern, &key),
+                "pattern: {:?}, key: {:?}",
+                pattern,
+                key
+            );
+        }
+
+        #[test]
+        fn trie_matches_like_topic_matches(
+            patterns in prop::collection::vec(pattern_strategy(), 1..10),
+            key in prop::collection::vec(prop::sample::select(&["a", "b", "c"][..]), 1..10),
+        ) {
+            let pattern_strings = patterns.iter().map(|pattern| pattern.join(".")).collect::<Vec<_>>();
+            let pattern_strs = pattern_strings.iter().map(String::as_str).collect::<Vec<_>>();
+
+            let expected = patterns
+                .iter()
+                .enumerate()
+                .filter(|(_, pattern)| topic_matches(pattern, &key))
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+
+            prop_assert_eq!(
+                matches(&pattern_strs, &key.join(".")),
+                expected,
+                "patterns: {:?}, key: {:?}",
+                pattern_strings,
+                key
+            );
+        }
+    }
+}
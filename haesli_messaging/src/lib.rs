@@ -3,7 +3,9 @@
 
 use haesli_core::error::ProtocolError;
 
+mod log_writer;
 pub mod methods;
+pub mod persistence;
 mod queue_worker;
 mod routing;
 
@@ -0,0 +1,233 @@
+use std::{sync::Arc, time::Instant};
+
+use haesli_store::{MessageLog, RecordId};
+use parking_lot::Mutex;
+use tokio::{
+    sync::{mpsc, oneshot, OwnedSemaphorePermit, Semaphore},
+    task, time,
+};
+use tracing::{error, info};
+
+/// How many appended messages can wait for the log writer before the queue worker has to wait
+const APPEND_CAPACITY: usize = 1024;
+
+/// A handle to the task that writes the message log of a durable queue.
+///
+/// The log is written on the blocking thread pool, so that neither the queue worker nor the
+/// channels acknowledging messages wait for the disk. Everything that was sent to the writer while
+/// it was busy is written together and synced once.
+#[derive(Debug)]
+pub(crate) struct LogWriter {
+    /// The name of the log, which is used to open it again after a restart
+    name: String,
+    /// The ID of the next appended record. Locked while the append is sent, so that the writer
+    /// gets the records in the order of their IDs.
+    next_id: Mutex<RecordId>,
+    commands: mpsc::UnboundedSender<Command>,
+    append_permits: Arc<Semaphore>,
+}
+
+/// Resolves once an appended record is on the disk as the sync policy demands. Fails if the
+/// record couldn't be written, in which case it is not in the log.
+pub(crate) type Written = oneshot::Receiver<()>;
+
+#[derive(Debug)]
+enum Command {
+    Append {
+        id: RecordId,
+        payload: Vec<u8>,
+        written: oneshot::Sender<()>,
+        _permit: OwnedSemaphorePermit,
+    },
+    Remove(RecordId),
+    Delete,
+}
+
+impl LogWriter {
+    pub(crate) fn start(name: String, log: MessageLog) -> Self {
+        let (commands, command_recv) = mpsc::unbounded_channel();
+        let next_id = Mutex::new(log.next_id());
+
+        tokio::spawn(write_log(log, command_recv));
+
+        Self {
+            name,
+            next_id,
+            commands,
+            append_permits: Arc::new(Semaphore::new(APPEND_CAPACITY)),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Appends a record to the log. Returns its ID right away, together with a [`Written`] that
+    /// tells when it is on the disk.
+    pub(crate) async fn append(&self, payload: Vec<u8>) -> (RecordId, Written) {
+        let permit = Arc::clone(&self.append_permits)
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+
+        let mut next_id = self.next_id.lock();
+        let id = *next_id;
+        *next_id += 1;
+
+        let (written, written_recv) = oneshot::channel();
+
+        // if the writer is gone, the record is reported as not written
+        let _ = self.commands.send(Command::Append {
+            id,
+            payload,
+            written,
+            _permit: permit,
+        });
+
+        (id, written_recv)
+    }
+
+    /// Removes a record from the log, after the records that were appended before are written
+    pub(crate) fn remove(&self, id: RecordId) {
+        let _ = self.commands.send(Command::Remove(id));
+    }
+
+    /// Deletes the log once everything that was sent before is done, and stops the writer
+    pub(crate) fn delete(&self) {
+        let _ = self.commands.send(Command::Delete);
+    }
+}
+
+async fn write_log(log: MessageLog, mut command_recv: mpsc::UnboundedReceiver<Command>) {
+    let mut writer = Writer {
+        log,
+        unsynced: Vec::new(),
+        sync_failed: false,
+    };
+
+    loop {
+        let deadline = writer.sync_deadline();
+
+        let batch = tokio::select! {
+            command = command_recv.recv() => {
+                let Some(command) = command else {
+                    return;
+                };
+                let mut batch = vec![command];
+                while let Ok(command) = command_recv.try_recv() {
+                    batch.push(command);
+                }
+                batch
+            }
+            // nothing was sent in time, so the records that are waiting are synced on their own
+            () = time::sleep_until(deadline.unwrap_or_else(Instant::now).into()), if deadline.is_some() => {
+                Vec::new()
+            }
+        };
+
+        match task::spawn_blocking(move || writer.write(batch)).await {
+            Ok(Some(written)) => writer = written,
+            Ok(None) => return,
+            Err(err) => {
+                error!(?err, "Message log writer panicked");
+                return;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Writer {
+    log: MessageLog,
+    /// The records that have been written, but are not synced yet
+    unsynced: Vec<(RecordId, oneshot::Sender<()>)>,
+    /// Set when the last sync failed, so that it is only tried again with the next batch instead
+    /// of right away
+    sync_failed: bool,
+}
+
+impl Writer {
+    fn sync_deadline(&self) -> Option<Instant> {
+        if self.sync_failed {
+            None
+        } else {
+            self.log.sync_deadline()
+        }
+    }
+
+    /// Writes the commands to the log and syncs it once for all of them if the sync policy says
+    /// so. Returns the writer, or `None` if the log has been deleted.
+    fn write(mut self, batch: Vec<Command>) -> Option<Self> {
+        for command in batch {
+            match command {
+                Command::Append {
+                    id,
+                    payload,
+                    written,
+                    ..
+                } => match self.log.append(id, &payload) {
+                    Ok(()) => self.unsynced.push((id, written)),
+                    // dropping the sender tells the queue worker that the record is not in the log
+                    Err(err) => {
+                        error!(?err, log = %self.log.dir().display(), %id, "Failed to append message to message log");
+                    }
+                },
+                Command::Remove(id) => {
+                    // if the removal gets lost, the message is delivered again after a restart
+                    if let Err(err) = self.log.remove(id) {
+                        error!(?err, log = %self.log.dir().display(), %id, "Failed to remove message from message log");
+                    }
+                }
+                Command::Delete => {
+                    // the messages are gone together with the queue anyway
+                    self.report_written();
+
+                    let dir = self.log.dir().to_owned();
+                    match self.log.delete() {
+                        Ok(()) => info!(log = %dir.display(), "Deleted message log"),
+                        Err(err) => {
+                            error!(?err, log = %dir.display(), "Failed to delete message log")
+                        }
+                    }
+                    return None;
+                }
+            }
+        }
+
+        self.sync_failed = false;
+        match self.log.sync_deadline() {
+            Some(deadline) if deadline <= Instant::now() => {
+                if let Err(err) = self.log.sync() {
+                    error!(?err, log = %self.log.dir().display(), "Failed to sync message log");
+                    self.sync_failed = true;
+                    self.remove_unsynced();
+                    return Some(self);
+                }
+            }
+            // the records are synced later, and reported as written then
+            Some(_) => return Some(self),
+            // everything is synced, or syncing is left to the operating system
+            None => {}
+        }
+
+        self.report_written();
+
+        Some(self)
+    }
+
+    fn report_written(&mut self) {
+        for (_, written) in self.unsynced.drain(..) {
+            let _ = written.send(());
+        }
+    }
+
+    /// Removes the records that might not be on the disk, because the queue worker drops their
+    /// messages. If the removals get lost as well, the messages show up again after a restart.
+    fn remove_unsynced(&mut self) {
+        for (id, _) in std::mem::take(&mut self.unsynced) {
+            if let Err(err) = self.log.remove(id) {
+                error!(?err, log = %self.log.dir().display(), %id, "Failed to remove unsynced message from message log");
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use haesli_core::{
+    connection::Channel,
+    delivery::{self, Unacked},
+    error::ChannelException,
+    methods::{BasicAck, BasicNack, BasicReject, DeliveryTag},
+    transaction::Settlement,
+};
+use tracing::debug;
+
+use crate::{methods::consume, persistence, Result};
+
+pub fn ack(channel: Channel, basic_ack: BasicAck) -> Result<()> {
+    let BasicAck {
+        delivery_tag,
+        multiple,
+    } = basic_ack;
+
+    settle(&channel, delivery_tag, multiple, Settlement::Ack)
+}
+
+pub fn reject(channel: Channel, basic_reject: BasicReject) -> Result<()> {
+    let BasicReject {
+        delivery_tag,
+        requeue,
+    } = basic_reject;
+
+    settle(
+        &channel,
+        delivery_tag,
+        false,
+        Settlement::Reject { requeue },
+    )
+}
+
+/// Basic.Nack is a RabbitMQ extension that works like Basic.Reject, but can reject multiple messages
+pub fn nack(channel: Channel, basic_nack: BasicNack) -> Result<()> {
+    let BasicNack {
+        delivery_tag,
+        multiple,
+        requeue,
+    } = basic_nack;
+
+    settle(
+        &channel,
+        delivery_tag,
+        multiple,
+        Settlement::Reject { requeue },
+    )
+}
+
+/// Settles the messages referenced by the delivery tag. In transaction mode, the tag is only
+/// validated and the messages are settled once the transaction is committed.
+fn settle(
+    channel: &Channel,
+    delivery_tag: DeliveryTag,
+    multiple: bool,
+    settlement: Settlement,
+) -> Result<()> {
+    let mut transaction = channel.transaction.lock();
+
+    let Some(transaction) = transaction.as_mut() else {
+        let settled = take_unacked(channel, delivery_tag, multiple)?;
+        apply_settlement(channel, settled, settlement);
+        return Ok(());
+    };
+
+    // a message that is settled in the transaction already counts as acknowledged
+    if delivery_tag != 0 && transaction.is_settled(delivery_tag) {
+        return Err(ChannelException::PreconditionFailed.into());
+    }
+
+    let mut tags = channel
+        .deliveries
+        .lock()
+        .tags(delivery_tag, multiple)
+        .ok_or(ChannelException::PreconditionFailed)?;
+
+    tags.retain(|tag| !transaction.is_settled(*tag));
+
+    debug!(%delivery_tag, %multiple, amount = %tags.len(), ?settlement, "Settled messages in transaction");
+
+    transaction.settle(tags, settlement);
+
+    Ok(())
+}
+
+/// Acknowledges or rejects messages that have been removed from the unacked messages.
+pub fn apply_settlement(channel: &Channel, settled: Vec<Unacked>, settlement: Settlement) {
+    match settlement {
+        Settlement::Ack => {
+            debug!(amount = %settled.len(), "Acknowledged messages");
+            remove_persisted(&settled);
+        }
+        Settlement::Reject { requeue } => settle_rejected(settled, requeue),
+    }
+
+    consume::capacity_available(channel);
+}
+
+fn take_unacked(
+    channel: &Channel,
+    delivery_tag: DeliveryTag,
+    multiple: bool,
+) -> Result<Vec<Unacked>> {
+    channel
+        .deliveries
+        .lock()
+        .remove(delivery_tag, multiple)
+        // 1.8.3.13 - the server MUST validate that a non-zero delivery-tag refers to a delivered message
+        .ok_or_else(|| ChannelException::PreconditionFailed.into())
+}
+
+fn remove_persisted(settled: &[Unacked]) {
+    for unacked in settled {
+        persistence::remove_message(&unacked.queue, unacked.record);
+    }
+}
+
+fn settle_rejected(rejected: Vec<Unacked>, requeue: bool) {
+    debug!(%requeue, amount = %rejected.len(), "Rejected messages");
+
+    if !requeue {
+        // we don't support dead lettering, so the messages are simply dropped
+        remove_persisted(&rejected);
+        return;
+    }
+
+    delivery::requeue(rejected);
+}
@@ -0,0 +1,32 @@
+use std::ops::Not;
+
+use haesli_core::{
+    confirm::Confirms,
+    connection::Channel,
+    error::ChannelException,
+    methods::{ConfirmSelect, ConfirmSelectOk, Method},
+};
+use tracing::debug;
+
+use crate::methods::MethodResponse;
+
+pub fn select(channel: Channel, confirm_select: ConfirmSelect) -> MethodResponse {
+    let ConfirmSelect { no_wait } = confirm_select;
+
+    // a channel can't use publisher confirms and transactions at the same time
+    if channel.transaction.lock().is_some() {
+        return Err(ChannelException::PreconditionFailed.into());
+    }
+
+    // selecting confirm mode again keeps counting the published messages
+    channel
+        .confirms
+        .lock()
+        .get_or_insert_with(Confirms::default);
+
+    debug!(channel = %channel.num, "Enabled publisher confirms");
+
+    Ok(no_wait
+        .not()
+        .then_some(Method::ConfirmSelectOk(ConfirmSelectOk)))
+}
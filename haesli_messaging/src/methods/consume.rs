@@ -4,12 +4,15 @@ use haesli_core::{
     amqp_todo,
     connection::Channel,
     consumer::{Consumer, ConsumerId},
-    error::ChannelException,
-    methods::{BasicConsume, BasicConsumeOk, Method},
+    error::{ChannelException, ConException},
+    methods::{
+        BasicCancel, BasicCancelOk, BasicConsume, BasicConsumeOk, BasicQos, BasicQosOk, Method,
+    },
+    queue::{Queue, QueueEvent},
 };
-use tracing::info;
+use tracing::{debug, info};
 
-use crate::methods::MethodResponse;
+use crate::methods::{queue, MethodResponse};
 
 pub fn consume(channel: Channel, basic_consume: BasicConsume) -> MethodResponse {
     let BasicConsume {
@@ -22,7 +25,7 @@ pub fn consume(channel: Channel, basic_consume: BasicConsume) -> MethodResponse
         ..
     } = basic_consume;
 
-    if no_local || exclusive || no_ack {
+    if no_local || exclusive {
         amqp_todo!();
     }
 
@@ -34,6 +37,19 @@ pub fn consume(channel: Channel, basic_consume: BasicConsume) -> MethodResponse
         consumer_tag
     };
 
+    let tag_in_use = channel
+        .connection
+        .consuming
+        .lock()
+        .iter()
+        .any(|consumer| consumer.channel.id == channel.id && consumer.tag == consumer_tag);
+
+    if tag_in_use {
+        return Err(ConException::NotAllowed.into());
+    }
+
+    let prefetch_count = channel.prefetch.lock().consumer;
+
     let mut global_data = global_data.lock();
 
     let queue = global_data
@@ -41,20 +57,101 @@ pub fn consume(channel: Channel, basic_consume: BasicConsume) -> MethodResponse
         .get_mut(queue_name.as_str())
         .ok_or(ChannelException::NotFound)?;
 
+    queue::check_exclusive(queue, &channel)?;
+
     let consumer = Consumer {
         id: ConsumerId::random(),
         tag: consumer_tag.clone(),
         channel: Arc::clone(&channel),
         queue: Arc::clone(queue),
+        no_ack,
+        prefetch_count,
     };
 
     queue.consumers.lock().insert(consumer.id, consumer.clone());
 
+    // the queue might already contain messages for the new consumer
+    let _ = queue.event_send.send(QueueEvent::ConsumerAdded);
+
     channel.connection.consuming.lock().push(consumer);
 
     info!(%queue_name, %consumer_tag, "Consumer started consuming");
 
     Ok(no_wait
         .not()
-        .then(|| Method::BasicConsumeOk(BasicConsumeOk { consumer_tag })))
+        .then_some(Method::BasicConsumeOk(BasicConsumeOk { consumer_tag })))
+}
+
+pub fn cancel(channel: Channel, basic_cancel: BasicCancel) -> MethodResponse {
+    let BasicCancel {
+        consumer_tag,
+        no_wait,
+    } = basic_cancel;
+
+    let cancelled = {
+        let mut consuming = channel.connection.consuming.lock();
+        let position = consuming
+            .iter()
+            .position(|consumer| consumer.channel.id == channel.id && consumer.tag == consumer_tag);
+        position.map(|position| consuming.remove(position))
+    };
+
+    // cancelling an unknown consumer is not an error
+    if let Some(consumer) = cancelled {
+        consumer.queue.remove_consumer(consumer.id);
+        info!(queue_name = %consumer.queue.name, %consumer_tag, "Consumer cancelled");
+    }
+
+    Ok(no_wait
+        .not()
+        .then_some(Method::BasicCancelOk(BasicCancelOk { consumer_tag })))
+}
+
+pub fn qos(channel: Channel, basic_qos: BasicQos) -> MethodResponse {
+    let BasicQos {
+        prefetch_size,
+        prefetch_count,
+        global,
+    } = basic_qos;
+
+    if prefetch_size != 0 {
+        amqp_todo!();
+    }
+
+    {
+        let mut prefetch = channel.prefetch.lock();
+        // like RabbitMQ, `global` decides whether the limit is shared by the whole channel,
+        // or applies to each consumer that is started on the channel afterwards
+        if global {
+            prefetch.channel = prefetch_count;
+        } else {
+            prefetch.consumer = prefetch_count;
+        }
+    }
+
+    debug!(%prefetch_count, %global, "Set prefetch limit");
+
+    // the limit might have been raised
+    capacity_available(&channel);
+
+    Ok(Some(Method::BasicQosOk(BasicQosOk)))
+}
+
+/// Notifies the queues consumed on the channel that they might be able to deliver more messages.
+pub(super) fn capacity_available(channel: &Channel) {
+    let mut queues = Vec::<Queue>::new();
+
+    for consumer in channel.connection.consuming.lock().iter() {
+        if consumer.channel.id == channel.id
+            && !queues
+                .iter()
+                .any(|queue| Arc::ptr_eq(queue, &consumer.queue))
+        {
+            queues.push(consumer.queue.clone());
+        }
+    }
+
+    for queue in queues {
+        let _ = queue.event_send.send(QueueEvent::CapacityAvailable);
+    }
 }
@@ -1,17 +1,22 @@
-use std::{collections::HashMap, ops::Not};
+use std::{collections::HashMap, mem, ops::Not};
 
 use haesli_core::{
     amqp_todo,
     connection::Channel,
-    error::ConException,
-    exchange::{Exchange, ExchangeName, ExchangeType},
-    methods::{ExchangeDeclare, ExchangeDeclareOk, Method},
+    error::{ChannelException, ConException},
+    exchange::{self, Destination, Exchange, ExchangeName, ExchangeType},
+    methods::{
+        ExchangeBind, ExchangeBindOk, ExchangeDeclare, ExchangeDeclareOk, ExchangeDelete,
+        ExchangeDeleteOk, ExchangeUnbind, ExchangeUnbindOk, Method, Table,
+    },
+    GlobalDataInner,
 };
-use tracing::info;
+use haesli_datastructure::TopicTrie;
+use tracing::{debug, info};
 
-use crate::methods::MethodResponse;
+use crate::{methods::MethodResponse, persistence, routing, Result};
 
-fn parse_exchange_type(str: &str) -> Option<ExchangeType> {
+pub(crate) fn parse_exchange_type(str: &str) -> Option<ExchangeType> {
     match str {
         "direct" => Some(ExchangeType::Direct {
             bindings: HashMap::new(),
@@ -20,6 +25,9 @@ fn parse_exchange_type(str: &str) -> Option<ExchangeType> {
             bindings: Vec::new(),
         }),
         "topic" => Some(ExchangeType::Topic {
+            bindings: TopicTrie::new(),
+        }),
+        "headers" => Some(ExchangeType::Headers {
             bindings: Vec::new(),
         }),
         _ => None,
@@ -32,39 +40,248 @@ pub fn declare(channel: Channel, exchange_declare: ExchangeDeclare) -> MethodRes
         r#type: kind,
         passive,
         durable,
+        auto_delete,
         no_wait,
         arguments,
         ..
     } = exchange_declare;
 
+    let name = ExchangeName::new(name.into());
+
+    // a passive declare only checks whether the exchange exists, the other fields are ignored
+    if passive {
+        if !channel.global_data.lock().exchanges.contains_key(&name) {
+            return Err(ChannelException::NotFound.into());
+        }
+
+        return Ok(no_wait
+            .not()
+            .then_some(Method::ExchangeDeclareOk(ExchangeDeclareOk)));
+    }
+
     if !arguments.is_empty() {
         amqp_todo!();
     }
 
-    // todo: implement durable
+    let kind = parse_exchange_type(&kind).ok_or(ConException::CommandInvalid)?;
 
-    if passive {
-        amqp_todo!();
+    {
+        let mut global_data = channel.global_data.lock();
+
+        if let Some(exchange) = global_data.exchanges.get(&name) {
+            if mem::discriminant(&exchange.kind) != mem::discriminant(&kind)
+                || exchange.durable != durable
+                || exchange.auto_delete != auto_delete
+            {
+                return Err(ChannelException::PreconditionFailed.into());
+            }
+
+            debug!(%name, "Declaring exchange that already exists");
+        } else {
+            info!(%name, "Creating exchange");
+
+            let exchange = Exchange {
+                name: name.clone(),
+                durable,
+                auto_delete,
+                kind,
+            };
+
+            global_data.exchanges.insert(name, exchange);
+
+            if durable {
+                persistence::save_topology(&global_data);
+            }
+        }
     }
 
-    let name = ExchangeName::new(name.into());
+    Ok(no_wait
+        .not()
+        .then_some(Method::ExchangeDeclareOk(ExchangeDeclareOk)))
+}
 
-    let kind = parse_exchange_type(&kind).ok_or(ConException::CommandInvalid)?;
+pub fn delete(channel: Channel, exchange_delete: ExchangeDelete) -> MethodResponse {
+    let ExchangeDelete {
+        exchange: name,
+        if_unused,
+        no_wait,
+        ..
+    } = exchange_delete;
 
-    info!(%name, "Creating exchange");
+    if exchange::is_predeclared(&name) {
+        return Err(ChannelException::AccessRefused.into());
+    }
 
-    let exchange = Exchange {
-        name: name.clone(),
-        durable,
-        kind,
-    };
+    {
+        let mut global_data = channel.global_data.lock();
+
+        let exchange = global_data
+            .exchanges
+            .get(name.as_str())
+            .ok_or(ChannelException::NotFound)?;
+
+        if if_unused && !routing::is_unused(exchange) {
+            return Err(ChannelException::PreconditionFailed.into());
+        }
+
+        delete_exchange(&mut global_data, &name);
+    }
+
+    info!(%name, "Deleted exchange");
+
+    Ok(no_wait
+        .not()
+        .then_some(Method::ExchangeDeleteOk(ExchangeDeleteOk)))
+}
+
+pub fn bind(channel: Channel, exchange_bind: ExchangeBind) -> MethodResponse {
+    let ExchangeBind {
+        destination,
+        source,
+        routing_key,
+        no_wait,
+        arguments,
+        ..
+    } = exchange_bind;
+
+    {
+        let mut global_data = channel.global_data.lock();
+
+        let (source_exchange, destination) =
+            source_and_destination(&mut global_data, &source, &destination)?;
+
+        routing::bind(
+            source_exchange,
+            routing_key.clone(),
+            arguments,
+            destination.clone(),
+        )?;
+
+        if persistence::is_durable_binding(&global_data, &source, &destination) {
+            persistence::save_topology(&global_data);
+        }
+    }
+
+    debug!(%source, %destination, %routing_key, "Bound exchange");
+
+    Ok(no_wait
+        .not()
+        .then_some(Method::ExchangeBindOk(ExchangeBindOk)))
+}
+
+pub fn unbind(channel: Channel, exchange_unbind: ExchangeUnbind) -> MethodResponse {
+    let ExchangeUnbind {
+        destination,
+        source,
+        routing_key,
+        no_wait,
+        arguments,
+        ..
+    } = exchange_unbind;
 
     {
         let mut global_data = channel.global_data.lock();
-        global_data.exchanges.entry(name).or_insert(exchange);
+
+        let (_, destination) = source_and_destination(&mut global_data, &source, &destination)?;
+
+        unbind_from(
+            &mut global_data,
+            &source,
+            &routing_key,
+            arguments,
+            &destination,
+        );
+
+        if persistence::is_durable_binding(&global_data, &source, &destination) {
+            persistence::save_topology(&global_data);
+        }
     }
 
+    debug!(%source, %destination, %routing_key, "Unbound exchange");
+
     Ok(no_wait
         .not()
-        .then(|| Method::ExchangeDeclareOk(ExchangeDeclareOk)))
+        .then_some(Method::ExchangeUnbindOk(ExchangeUnbindOk)))
+}
+
+/// Removes a binding from the exchange. Auto-delete exchanges are deleted when their last binding
+/// is removed.
+pub(crate) fn unbind_from(
+    global_data: &mut GlobalDataInner,
+    exchange_name: &str,
+    routing_key: &str,
+    arguments: Table,
+    destination: &Destination,
+) {
+    let Some(exchange) = global_data.exchanges.get_mut(exchange_name) else {
+        return;
+    };
+
+    let was_used = !routing::is_unused(exchange);
+
+    routing::unbind(exchange, routing_key, arguments, destination);
+
+    if was_used && exchange.auto_delete && routing::is_unused(exchange) {
+        info!(%exchange_name, "Deleting auto-delete exchange without bindings");
+        delete_exchange(global_data, exchange_name);
+    }
+}
+
+/// Removes all bindings of the destination from every exchange, for example because it's deleted.
+/// Auto-delete exchanges that lose their last binding are deleted as well.
+pub(crate) fn unbind_everywhere(global_data: &mut GlobalDataInner, destination: &Destination) {
+    let mut unused = Vec::new();
+
+    for exchange in global_data.exchanges.values_mut() {
+        let was_used = !routing::is_unused(exchange);
+
+        routing::unbind_all(exchange, destination);
+
+        if was_used && exchange.auto_delete && routing::is_unused(exchange) {
+            unused.push(exchange.name.clone());
+        }
+    }
+
+    for exchange_name in unused {
+        info!(%exchange_name, "Deleting auto-delete exchange without bindings");
+        delete_exchange(global_data, &exchange_name);
+    }
+}
+
+/// Removes the exchange, together with the bindings of other exchanges to it
+fn delete_exchange(global_data: &mut GlobalDataInner, name: &str) {
+    let Some(exchange) = global_data.exchanges.remove(name) else {
+        return;
+    };
+
+    unbind_everywhere(global_data, &Destination::Exchange(exchange.name));
+
+    if exchange.durable {
+        persistence::save_topology(global_data);
+    }
+}
+
+/// Looks up the exchanges of an exchange binding, which both have to exist
+fn source_and_destination<'a>(
+    global_data: &'a mut GlobalDataInner,
+    source: &str,
+    destination: &str,
+) -> Result<(&'a mut Exchange, Destination)> {
+    // the default exchange only has the implicit bindings of every queue
+    if source.is_empty() || destination.is_empty() {
+        return Err(ChannelException::AccessRefused.into());
+    }
+
+    let destination = global_data
+        .exchanges
+        .get_key_value(destination)
+        .map(|(name, _)| Destination::Exchange(name.clone()))
+        .ok_or(ChannelException::NotFound)?;
+
+    let source = global_data
+        .exchanges
+        .get_mut(source)
+        .ok_or(ChannelException::NotFound)?;
+
+    Ok((source, destination))
 }
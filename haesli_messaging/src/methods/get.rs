@@ -0,0 +1,76 @@
+use haesli_core::{
+    connection::{Channel, ConnectionEvent},
+    delivery::Unacked,
+    error::ChannelException,
+    methods::{BasicGet, BasicGetEmpty, BasicGetOk, Method},
+};
+use tracing::debug;
+
+use crate::{methods::queue, persistence, Result};
+
+/// Takes a single message from the queue. Unlike the other methods, the response can have content.
+pub fn get(channel: Channel, basic_get: BasicGet) -> Result<ConnectionEvent> {
+    let BasicGet {
+        queue: queue_name,
+        no_ack,
+        ..
+    } = basic_get;
+
+    let queue = channel
+        .global_data
+        .lock()
+        .queues
+        .get(queue_name.as_str())
+        .cloned()
+        .ok_or(ChannelException::NotFound)?;
+
+    queue::check_exclusive(&queue, &channel)?;
+
+    // the lock is held until the message is tracked, so that it can't be acked before that
+    let mut deliveries = channel.deliveries.lock();
+
+    let Some(message) = queue.messages.try_get() else {
+        debug!(%queue_name, "Queue is empty");
+        return Ok(ConnectionEvent::Method(
+            channel.num,
+            Box::new(Method::BasicGetEmpty(BasicGetEmpty {
+                reserved_1: String::new(),
+            })),
+        ));
+    };
+
+    let delivery_tag = deliveries.next_tag();
+
+    if no_ack {
+        persistence::remove_message(&queue, message.record);
+    } else {
+        deliveries.track(
+            delivery_tag,
+            Unacked {
+                message: message.message.clone(),
+                queue: queue.clone(),
+                consumer: None,
+                record: message.record,
+            },
+        );
+    }
+
+    let routing = &message.message.routing;
+
+    let method = Box::new(Method::BasicGetOk(BasicGetOk {
+        delivery_tag,
+        redelivered: message.redelivered,
+        exchange: routing.exchange.clone(),
+        routing_key: routing.routing_key.clone(),
+        message_count: u32::try_from(queue.messages.len()).unwrap_or(u32::MAX),
+    }));
+
+    debug!(%queue_name, %delivery_tag, "Got message from queue");
+
+    Ok(ConnectionEvent::MethodContent(
+        channel.num,
+        method,
+        message.message.header.clone(),
+        message.message.content.clone(),
+    ))
+}
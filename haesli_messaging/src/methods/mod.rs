@@ -1,10 +1,20 @@
+mod ack;
+mod confirm;
 mod consume;
-mod exchange;
+pub(crate) mod exchange;
+mod get;
 mod publish;
-mod queue;
+pub(crate) mod queue;
+mod tx;
 
-use haesli_core::{amqp_todo, connection::Channel, error::ConException, methods::Method};
-pub use publish::publish;
+use haesli_core::{
+    amqp_todo,
+    connection::{Channel, Connection, ConnectionEvent},
+    error::ConException,
+    message::Message,
+    methods::Method,
+    HandlerFuture, SingleVec,
+};
 use tracing::{info, warn};
 
 use crate::Result;
@@ -12,32 +22,69 @@ use crate::Result;
 type MethodResponse = Result<Option<Method>>;
 
 /// This is the entrypoint of methods not handled by the connection itself.
-/// Note that Basic.Publish is *not* sent here, but to [`handle_basic_publish`](crate::handle_basic_publish)
-pub fn handle_method(channel: Channel, method: Method) -> Result<Option<Method>> {
+/// Note that Basic.Publish is *not* sent here, but to [`handle_basic_publish`]
+///
+/// Returns the responses that should be sent back on the channel, usually at most one.
+pub fn handle_method(
+    channel: Channel,
+    method: Method,
+) -> HandlerFuture<SingleVec<ConnectionEvent>> {
+    Box::pin(dispatch_method(channel, method))
+}
+
+/// This is the entrypoint of Basic.Publish, once the content of the message has been received.
+///
+/// Returns a Basic.Return that should be sent back on the channel if the message is returned.
+pub fn handle_basic_publish(
+    channel: Channel,
+    message: Message,
+) -> HandlerFuture<Option<ConnectionEvent>> {
+    Box::pin(publish::publish(channel, message))
+}
+
+async fn dispatch_method(channel: Channel, method: Method) -> Result<SingleVec<ConnectionEvent>> {
     use Method::*;
 
     info!(?method, "Handling method");
 
+    let channel_num = channel.num;
+
     let response = match method {
         ExchangeDeclare(exchange_declare) => exchange::declare(channel, exchange_declare)?,
-        ExchangeDelete(_) => amqp_todo!(),
+        ExchangeDelete(exchange_delete) => exchange::delete(channel, exchange_delete)?,
+        ExchangeBind(exchange_bind) => exchange::bind(channel, exchange_bind)?,
+        ExchangeUnbind(exchange_unbind) => exchange::unbind(channel, exchange_unbind)?,
         QueueDeclare(queue_declare) => queue::declare(channel, queue_declare)?,
         QueueBind(queue_bind) => queue::bind(channel, queue_bind)?,
-        QueueUnbind(_) => amqp_todo!(),
-        QueuePurge(_) => amqp_todo!(),
-        QueueDelete(_) => amqp_todo!(),
-        BasicQos(_) => amqp_todo!(),
+        QueueUnbind(queue_unbind) => queue::unbind(channel, queue_unbind)?,
+        QueuePurge(queue_purge) => queue::purge(channel, queue_purge)?,
+        QueueDelete(queue_delete) => queue::delete(channel, queue_delete)?,
+        BasicQos(basic_qos) => consume::qos(channel, basic_qos)?,
         BasicConsume(consume) => consume::consume(channel, consume)?,
-        BasicCancel(_) => amqp_todo!(),
-        BasicGet(_) => amqp_todo!(),
-        BasicAck(_) => amqp_todo!(),
-        BasicReject(_) => amqp_todo!(),
+        BasicCancel(basic_cancel) => consume::cancel(channel, basic_cancel)?,
+        // the response to a Basic.Cancel sent by the server, there is nothing left to do
+        BasicCancelOk(_) => None,
+        BasicGet(basic_get) => {
+            return get::get(channel, basic_get).map(|event| SingleVec::from_buf([event]))
+        }
+        BasicAck(basic_ack) => {
+            ack::ack(channel, basic_ack)?;
+            None
+        }
+        BasicReject(basic_reject) => {
+            ack::reject(channel, basic_reject)?;
+            None
+        }
+        BasicNack(basic_nack) => {
+            ack::nack(channel, basic_nack)?;
+            None
+        }
         BasicRecoverAsync(_) => amqp_todo!(),
         BasicRecover(_) => amqp_todo!(),
-        TxSelect(_) => amqp_todo!(),
-        TxSelectOk(_) => amqp_todo!(),
-        TxCommit(_) => amqp_todo!(),
-        TxRollback(_) => amqp_todo!(),
+        ConfirmSelect(confirm_select) => confirm::select(channel, confirm_select)?,
+        TxSelect(_) => tx::select(channel)?,
+        TxCommit(_) => return tx::commit(channel).await,
+        TxRollback(_) => tx::rollback(channel)?,
         BasicPublish(_) => {
             unreachable!("Basic.Publish is handled somewhere else because it has a body")
         }
@@ -51,19 +98,22 @@ pub fn handle_method(channel: Channel, method: Method) -> Result<Option<Method>>
         | ChannelCloseOk(_)
         | ExchangeDeclareOk(_)
         | ExchangeDeleteOk(_)
+        | ExchangeBindOk(_)
+        | ExchangeUnbindOk(_)
         | QueueDeclareOk(_)
         | QueueBindOk(_)
         | QueueUnbindOk(_)
         | QueuePurgeOk(_)
         | QueueDeleteOk(_)
         | BasicQosOk(_)
-        | BasicCancelOk(_)
         | BasicConsumeOk(_)
         | BasicReturn(_)
         | BasicDeliver(_)
         | BasicGetOk(_)
         | BasicGetEmpty(_)
         | BasicRecoverOk(_)
+        | ConfirmSelectOk(_)
+        | TxSelectOk(_)
         | TxCommitOk(_)
         | TxRollbackOk(_) => return Err(ConException::NotAllowed.into()), // only sent by server
         ConnectionStart(_) | ConnectionSecure(_) | ConnectionTune(_) | ConnectionOpen(_)
@@ -73,5 +123,13 @@ pub fn handle_method(channel: Channel, method: Method) -> Result<Option<Method>>
         }
     };
 
-    Ok(response)
+    Ok(response
+        .map(|method| ConnectionEvent::Method(channel_num, Box::new(method)))
+        .into_iter()
+        .collect())
+}
+
+/// Cleans up the things that belong to a connection after it has been closed.
+pub fn connection_closed(connection: Connection) {
+    queue::delete_exclusive(&connection);
 }
@@ -1,42 +1,146 @@
 use std::sync::Arc;
 
 use haesli_core::{
-    connection::Channel,
-    error::{ChannelException, ConException},
+    confirm::PendingConfirm,
+    connection::{Channel, ConnectionEvent},
+    error::ChannelException,
     message::Message,
-    queue::QueueEvent,
+    methods::{BasicReturn, FieldValue, Method},
+    queue::{Queue, QueuePublish},
 };
-use tracing::{debug, error};
+use tracing::debug;
 
 use crate::{routing, Result};
 
-pub fn publish(channel_handle: Channel, message: Message) -> Result<()> {
+/// Publishes the message to the queues it is routed to. In transaction mode, the message is only
+/// routed once the transaction is committed.
+///
+/// Waits until all queues have room for the message, so a publisher that is faster than its
+/// queues is slowed down instead of losing messages.
+///
+/// Returns a Basic.Return that should be sent back to the publisher if the message couldn't be
+/// routed to any queue and is mandatory.
+pub async fn publish(channel_handle: Channel, message: Message) -> Result<Option<ConnectionEvent>> {
     debug!(?message, "Publishing message");
 
-    let global_data = channel_handle.global_data.clone();
+    if let Some(transaction) = channel_handle.transaction.lock().as_mut() {
+        // the exchange still has to exist, so that the publisher gets the error right away
+        if !channel_handle
+            .global_data
+            .lock()
+            .exchanges
+            .contains_key(message.routing.exchange.as_str())
+        {
+            return Err(ChannelException::NotFound.into());
+        }
 
-    let routing = &message.routing;
+        transaction.publishes.push(message);
+        return Ok(None);
+    }
+
+    let routed = route(&channel_handle, message)?;
+    Ok(send(&channel_handle, routed).await)
+}
+
+/// A message together with the queues it is routed to
+pub(crate) struct RoutedMessage {
+    message: Message,
+    queues: Vec<Queue>,
+}
 
-    let global_data = global_data.lock();
+/// Looks up the queues the message is routed to, without sending it yet
+pub(crate) fn route(channel_handle: &Channel, message: Message) -> Result<RoutedMessage> {
+    let global_data = channel_handle.global_data.lock();
 
-    let exchange = &routing.exchange;
+    let routing = &message.routing;
 
     let exchange = global_data
         .exchanges
-        .get(exchange.as_str())
+        .get(routing.exchange.as_str())
         .ok_or(ChannelException::NotFound)?;
 
-    let queues =
-        routing::route_message(exchange, &routing.routing_key).ok_or(ChannelException::NotFound)?; // todo this isn't really correct but the tests pass ✔️
+    let headers = match message.header.property_fields.get("headers") {
+        Some(FieldValue::FieldTable(headers)) => Some(headers),
+        _ => None,
+    };
+
+    let queues = routing::route_message(
+        &global_data.exchanges,
+        exchange,
+        &routing.routing_key,
+        headers,
+    )
+    .unwrap_or_default();
+
+    drop(global_data);
+
+    Ok(RoutedMessage { message, queues })
+}
+
+/// Sends a routed message to its queues, waiting for room in each of them. Returns a Basic.Return
+/// for the publisher if the message is mandatory and wasn't routed to any queue.
+///
+/// If the channel is in confirm mode, the publisher gets a Basic.Ack once all queues have enqueued
+/// the message, or right away if it wasn't routed anywhere.
+pub(crate) async fn send(
+    channel_handle: &Channel,
+    routed: RoutedMessage,
+) -> Option<ConnectionEvent> {
+    let RoutedMessage { message, queues } = routed;
+    let routing = &message.routing;
+
+    let confirm = channel_handle.confirms.lock().as_mut().map(|confirms| {
+        PendingConfirm::new(
+            confirms.next_tag(),
+            channel_handle.num,
+            channel_handle.connection.confirm_sender.clone(),
+        )
+    });
+
+    if queues.is_empty() {
+        // the confirm is sent after the Basic.Return, which is sent directly by the transport
+        if let Some(confirm) = confirm {
+            confirm.confirm();
+        }
+
+        if !routing.mandatory {
+            debug!("Dropping unroutable message");
+            return None;
+        }
+
+        debug!("Returning unroutable mandatory message");
+
+        let method = Box::new(Method::BasicReturn(BasicReturn {
+            reply_code: 312,
+            reply_text: "no-route".to_owned(),
+            exchange: routing.exchange.clone(),
+            routing_key: routing.routing_key.clone(),
+        }));
+
+        return Some(ConnectionEvent::MethodContent(
+            channel_handle.num,
+            method,
+            message.header.clone(),
+            message.content.clone(),
+        ));
+    }
 
     for queue in queues {
-        queue
-            .event_send
-            .try_send(QueueEvent::PublishMessage(Arc::clone(&message)))
-            .map_err(|err| {
-                error!(?err, "Failed to send message to queue event queue");
-                ConException::InternalError
-            })?;
+        let publish = QueuePublish {
+            message: Arc::clone(&message),
+            confirm: confirm.as_ref().map(PendingConfirm::split),
+        };
+
+        // the queue task only stops once the queue has been deleted, so the message is dropped
+        // just like if the queue had been deleted before routing
+        if queue.publish_send.send(publish).await.is_err() {
+            debug!(queue = %queue.name, "Queue has been deleted, dropping message");
+        }
     }
-    Ok(())
+
+    if let Some(confirm) = confirm {
+        confirm.confirm();
+    }
+
+    None
 }
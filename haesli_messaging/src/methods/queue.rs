@@ -1,21 +1,29 @@
-use std::{
-    ops::Not,
-    sync::{atomic::AtomicUsize, Arc},
-};
+use std::{ops::Not, sync::Arc};
 
 use haesli_core::{
     amqp_todo,
-    connection::Channel,
+    connection::{Channel, Connection, ConnectionEvent, ConnectionId},
+    consumer::Consumer,
     error::ChannelException,
-    methods::{Method, QueueBind, QueueBindOk, QueueDeclare, QueueDeclareOk},
-    queue::{Queue, QueueDeletion, QueueId, QueueInner, QueueName},
-    GlobalData,
+    exchange::Destination,
+    methods::{
+        BasicCancel, Method, QueueBind, QueueBindOk, QueueDeclare, QueueDeclareOk, QueueDelete,
+        QueueDeleteOk, QueuePurge, QueuePurgeOk, QueueUnbind, QueueUnbindOk, Table,
+    },
+    queue::{Queue, QueueDeletion, QueueEvent, QueueId, QueueInner, QueueName, PUBLISH_CAPACITY},
+    GlobalData, GlobalDataInner,
 };
+use haesli_store::MessageLog;
 use parking_lot::Mutex;
 use tokio::sync::mpsc;
 use tracing::{debug, info};
 
-use crate::{methods::MethodResponse, queue_worker::QueueTask, routing, Result};
+use crate::{
+    methods::{exchange, MethodResponse},
+    persistence,
+    queue_worker::QueueTask,
+    routing, Result,
+};
 
 pub fn declare(channel: Channel, queue_declare: QueueDeclare) -> MethodResponse {
     let QueueDeclare {
@@ -38,75 +46,164 @@ pub fn declare(channel: Channel, queue_declare: QueueDeclare) -> MethodResponse
 
     let queue_name = QueueName::new(queue_name.into());
 
+    let global_data = channel.global_data.clone();
+
+    // a passive declare only checks whether the queue exists, the other fields are ignored
+    if passive {
+        let queue = global_data
+            .lock()
+            .queues
+            .get(&queue_name)
+            .cloned()
+            .ok_or(ChannelException::NotFound)?;
+
+        check_exclusive(&queue, &channel)?;
+
+        debug!(%queue_name, "Passively declared queue");
+
+        return Ok(no_wait.not().then(|| declare_ok(&queue)));
+    }
+
     if !arguments.is_empty() {
         amqp_todo!();
     }
 
-    // todo: implement durable, not checked here because it's the amqplib default
-
-    if passive {
-        amqp_todo!();
+    // creating a message log touches the disk, so it is done before taking the lock below and
+    // deleted again if the queue has been declared by someone else in the meantime
+    let mut log = None;
+    if durable && !exclusive && !global_data.lock().queues.contains_key(&queue_name) {
+        log = persistence::create_message_log()?;
     }
 
-    let global_data = channel.global_data.clone();
+    // the queue is looked up and created under the same lock, so that it is only created once
+    let mut global_data_lock = global_data.lock();
 
-    let queue = {
-        let global_data_lock = global_data.lock();
-        global_data_lock.queues.get(&queue_name).cloned()
-    };
+    let queue = if let Some(queue) = global_data_lock.queues.get(&queue_name).cloned() {
+        drop(global_data_lock);
+
+        if let Some(log) = log {
+            persistence::delete_unused_message_log(log);
+        }
+
+        check_exclusive(&queue, &channel)?;
+
+        let is_auto_delete = matches!(queue.deletion, QueueDeletion::Auto);
+
+        if queue.durable != durable
+            || queue.exclusive.is_some() != exclusive
+            || is_auto_delete != auto_delete
+        {
+            return Err(ChannelException::PreconditionFailed.into());
+        }
 
-    let queue = if let Some(queue) = queue {
         debug!(%queue_name, "Declaring queue that already exists");
         queue
     } else {
         info!(%queue_name, "Creating queue");
 
-        let (event_send, event_recv) = mpsc::channel(10);
+        if durable && !exclusive && log.is_none() {
+            // the queue has been deleted since it was looked up above
+            log = persistence::create_message_log()?;
+        }
 
-        let id = QueueId::random();
-        let queue = Arc::new(QueueInner {
-            id,
-            name: queue_name.clone(),
-            messages: haesli_datastructure::MessageQueue::new(),
+        let queue = create_queue(
+            &global_data,
+            &mut global_data_lock,
+            queue_name,
             durable,
-            exclusive: exclusive.then(|| channel.id),
-            deletion: if auto_delete {
-                QueueDeletion::Auto(AtomicUsize::default())
-            } else {
-                QueueDeletion::Manual
-            },
-            consumers: Mutex::default(),
-            event_send,
-        });
-
-        bind_queue(
-            global_data.clone(),
-            queue.clone(),
-            "",
-            queue_name.to_string(),
+            exclusive.then_some(channel.connection.id),
+            auto_delete,
+            log,
         )?;
 
-        {
-            let mut global_data_lock = global_data.lock();
-            global_data_lock
-                .queues
-                .insert(queue_name.clone(), queue.clone());
+        if durable && !exclusive {
+            persistence::save_topology(&global_data_lock);
         }
 
-        let queue_task = QueueTask::new(global_data, event_recv, queue.clone());
+        drop(global_data_lock);
 
-        tokio::spawn(async move { queue_task.start().await });
+        if exclusive {
+            channel
+                .connection
+                .exclusive_queues
+                .lock()
+                .push(queue.clone());
+        }
 
         queue
     };
 
-    Ok(no_wait.not().then(|| {
-        Method::QueueDeclareOk(QueueDeclareOk {
-            queue: queue_name.to_string(),
-            message_count: u32::try_from(queue.messages.len()).unwrap(),
-            consumer_count: u32::try_from(queue.consumers.lock().len()).unwrap(),
-        })
-    }))
+    Ok(no_wait.not().then(|| declare_ok(&queue)))
+}
+
+/// Creates a new queue, binds it to the default exchange and starts its queue task. The lock on
+/// the global data has to be held by the caller.
+pub(crate) fn create_queue(
+    global_data: &GlobalData,
+    global_data_lock: &mut GlobalDataInner,
+    queue_name: QueueName,
+    durable: bool,
+    exclusive: Option<ConnectionId>,
+    auto_delete: bool,
+    log: Option<MessageLog>,
+) -> Result<Queue> {
+    let (event_send, event_recv) = mpsc::unbounded_channel();
+    let (publish_send, publish_recv) = mpsc::channel(PUBLISH_CAPACITY);
+
+    let id = QueueId::random();
+    let queue = Arc::new(QueueInner {
+        id,
+        name: queue_name.clone(),
+        messages: haesli_datastructure::MessageQueue::new(),
+        durable,
+        exclusive,
+        deletion: if auto_delete {
+            QueueDeletion::Auto
+        } else {
+            QueueDeletion::Manual
+        },
+        consumers: Mutex::default(),
+        event_send,
+        publish_send,
+    });
+
+    if let Some(log) = log {
+        persistence::start_log_writer(&queue, log);
+    }
+
+    bind_queue(
+        global_data_lock,
+        queue.clone(),
+        "",
+        queue_name.to_string(),
+        Table::new(),
+    )?;
+
+    global_data_lock.queues.insert(queue_name, queue.clone());
+
+    let queue_task = QueueTask::new(global_data.clone(), event_recv, publish_recv, queue.clone());
+
+    tokio::spawn(async move { queue_task.start().await });
+
+    Ok(queue)
+}
+
+/// Exclusive queues can only be used by the connection that declared them
+pub(crate) fn check_exclusive(queue: &Queue, channel: &Channel) -> Result<()> {
+    match queue.exclusive {
+        Some(connection_id) if connection_id != channel.connection.id => {
+            Err(ChannelException::ResourceLocked.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn declare_ok(queue: &Queue) -> Method {
+    Method::QueueDeclareOk(QueueDeclareOk {
+        queue: queue.name.to_string(),
+        message_count: u32::try_from(queue.messages.len()).unwrap(),
+        consumer_count: u32::try_from(queue.consumers.lock().len()).unwrap(),
+    })
 }
 
 pub fn bind(channel_handle: Channel, queue_bind: QueueBind) -> MethodResponse {
@@ -119,43 +216,255 @@ pub fn bind(channel_handle: Channel, queue_bind: QueueBind) -> MethodResponse {
         ..
     } = queue_bind;
 
-    if !arguments.is_empty() {
-        amqp_todo!();
+    // the queue can't be deleted between looking it up and binding it
+    let mut global_data = channel_handle.global_data.lock();
+
+    let queue = global_data
+        .queues
+        .get(queue.as_str())
+        .ok_or(ChannelException::NotFound)?
+        .clone();
+
+    check_exclusive(&queue, &channel_handle)?;
+
+    bind_queue(
+        &mut global_data,
+        queue.clone(),
+        &exchange,
+        routing_key,
+        arguments,
+    )?;
+
+    if persistence::is_durable_binding(&global_data, &exchange, &Destination::Queue(queue)) {
+        persistence::save_topology(&global_data);
+    }
+
+    Ok(no_wait.not().then_some(Method::QueueBindOk(QueueBindOk)))
+}
+
+pub fn unbind(channel: Channel, queue_unbind: QueueUnbind) -> MethodResponse {
+    let QueueUnbind {
+        queue: queue_name,
+        exchange: exchange_name,
+        routing_key,
+        arguments,
+        ..
+    } = queue_unbind;
+
+    // every queue is bound to the default exchange with its name, which can't be changed
+    if exchange_name.is_empty() {
+        return Err(ChannelException::AccessRefused.into());
+    }
+
+    let mut global_data = channel.global_data.lock();
+
+    let queue = global_data
+        .queues
+        .get(queue_name.as_str())
+        .ok_or(ChannelException::NotFound)?
+        .clone();
+
+    check_exclusive(&queue, &channel)?;
+
+    if !global_data.exchanges.contains_key(exchange_name.as_str()) {
+        return Err(ChannelException::NotFound.into());
     }
 
+    let destination = Destination::Queue(queue);
+
+    exchange::unbind_from(
+        &mut global_data,
+        &exchange_name,
+        &routing_key,
+        arguments,
+        &destination,
+    );
+
+    if persistence::is_durable_binding(&global_data, &exchange_name, &destination) {
+        persistence::save_topology(&global_data);
+    }
+
+    debug!(%queue_name, %exchange_name, %routing_key, "Unbound queue");
+
+    Ok(Some(Method::QueueUnbindOk(QueueUnbindOk)))
+}
+
+pub fn delete(channel: Channel, queue_delete: QueueDelete) -> MethodResponse {
+    let QueueDelete {
+        queue: queue_name,
+        if_unused,
+        if_empty,
+        no_wait,
+        ..
+    } = queue_delete;
+
     let queue = {
-        let global_data = channel_handle.global_data.lock();
-        global_data
+        let mut global_data = channel.global_data.lock();
+
+        let queue = global_data
             .queues
-            .get(queue.as_str())
+            .get(queue_name.as_str())
             .ok_or(ChannelException::NotFound)?
-            .clone()
+            .clone();
+
+        check_exclusive(&queue, &channel)?;
+
+        if if_unused && !queue.consumers.lock().is_empty() {
+            return Err(ChannelException::PreconditionFailed.into());
+        }
+
+        if if_empty && !queue.messages.is_empty() {
+            return Err(ChannelException::PreconditionFailed.into());
+        }
+
+        // the conditions are checked under the same lock, so no consumer can be added in between
+        remove_queue(&mut global_data, &queue);
+
+        queue
     };
 
-    bind_queue(
-        channel_handle.global_data.clone(),
-        queue,
-        &exchange,
-        routing_key,
-    )?;
+    let message_count = shut_down_queue(&queue);
+    let message_count = u32::try_from(message_count).unwrap_or(u32::MAX);
+
+    if queue.exclusive.is_some() {
+        channel
+            .connection
+            .exclusive_queues
+            .lock()
+            .retain(|other| !Arc::ptr_eq(other, &queue));
+    }
+
+    info!(%queue_name, %message_count, "Deleted queue");
 
-    Ok(no_wait.not().then(|| Method::QueueBindOk(QueueBindOk)))
+    Ok(no_wait
+        .not()
+        .then_some(Method::QueueDeleteOk(QueueDeleteOk { message_count })))
+}
+
+pub fn purge(channel: Channel, queue_purge: QueuePurge) -> MethodResponse {
+    let QueuePurge {
+        queue: queue_name,
+        no_wait,
+        ..
+    } = queue_purge;
+
+    let queue = channel
+        .global_data
+        .lock()
+        .queues
+        .get(queue_name.as_str())
+        .cloned()
+        .ok_or(ChannelException::NotFound)?;
+
+    check_exclusive(&queue, &channel)?;
+
+    // messages that have been delivered but not acknowledged yet are not affected
+    let purged = queue.messages.purge();
+
+    for message in &purged {
+        persistence::remove_message(&queue, message.record);
+    }
+
+    let message_count = u32::try_from(purged.len()).unwrap_or(u32::MAX);
+
+    info!(%queue_name, %message_count, "Purged queue");
+
+    Ok(no_wait
+        .not()
+        .then_some(Method::QueuePurgeOk(QueuePurgeOk { message_count })))
+}
+
+/// Deletes the queue together with its bindings, consumers and messages, and stops its queue task.
+///
+/// Returns the number of messages that were still in the queue, or `None` if it has already been
+/// deleted.
+pub(crate) fn delete_queue(global_data: &GlobalData, queue: &Queue) -> Option<usize> {
+    let removed = remove_queue(&mut global_data.lock(), queue);
+
+    removed.then(|| shut_down_queue(queue))
+}
+
+/// Removes the queue and its bindings from the global data. Returns whether the queue was still
+/// there, in which case it has to be shut down with [`shut_down_queue`] after the lock is released.
+///
+/// Callers that only delete the queue under some condition check it while holding the same lock,
+/// so that for example no consumer can be added in between.
+pub(crate) fn remove_queue(global_data: &mut GlobalDataInner, queue: &Queue) -> bool {
+    let exists = global_data
+        .queues
+        .get(&queue.name)
+        .is_some_and(|other| Arc::ptr_eq(other, queue));
+
+    if !exists {
+        return false;
+    }
+
+    global_data.queues.remove(&queue.name);
+
+    exchange::unbind_everywhere(global_data, &Destination::Queue(queue.clone()));
+
+    if queue.durable && queue.exclusive.is_none() {
+        persistence::save_topology(global_data);
+    }
+
+    true
+}
+
+/// Cancels the consumers and drops the messages of a queue that has been removed, and stops its
+/// queue task. Returns the number of messages that were still in the queue.
+pub(crate) fn shut_down_queue(queue: &Queue) -> usize {
+    queue.cancel_consumers().into_iter().for_each(send_cancel);
+
+    let message_count = queue.messages.purge().len();
+
+    persistence::delete_message_log(queue);
+
+    let _ = queue.event_send.send(QueueEvent::Shutdown);
+
+    message_count
+}
+
+/// Tells the client that the server has cancelled its consumer. The Basic.Cancel is sent by a task
+/// that waits until the connection can take it, so that it doesn't get lost while the connection
+/// is busy.
+fn send_cancel(consumer: Consumer) {
+    let event = ConnectionEvent::Method(
+        consumer.channel.num,
+        Box::new(Method::BasicCancel(BasicCancel {
+            consumer_tag: consumer.tag,
+            no_wait: true,
+        })),
+    );
+    let event_sender = consumer.channel.event_sender.clone();
+
+    tokio::spawn(async move {
+        // this only fails if the connection has been closed, then there is nobody left to tell
+        let _ = event_sender.send(event).await;
+    });
+}
+
+/// Deletes the exclusive queues of a connection that has been closed
+pub(crate) fn delete_exclusive(connection: &Connection) {
+    let queues = std::mem::take(&mut *connection.exclusive_queues.lock());
+
+    for queue in queues {
+        if let Some(message_count) = delete_queue(&connection.global_data, &queue) {
+            info!(queue_name = %queue.name, %message_count, "Deleted exclusive queue of closed connection");
+        }
+    }
 }
 
 fn bind_queue(
-    global_data: GlobalData,
+    global_data: &mut GlobalDataInner,
     queue: Queue,
     exchange: &str,
     routing_key: String,
+    arguments: Table,
 ) -> Result<()> {
-    let mut global_data = global_data.lock();
-
     let exchange = global_data
         .exchanges
         .get_mut(exchange)
         .ok_or(ChannelException::NotFound)?;
 
-    routing::bind(exchange, routing_key, queue);
-
-    Ok(())
+    routing::bind(exchange, routing_key, arguments, Destination::Queue(queue))
 }
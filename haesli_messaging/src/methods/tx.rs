@@ -0,0 +1,187 @@
+use haesli_core::{
+    connection::{Channel, ConnectionEvent},
+    error::ChannelException,
+    methods::{Method, TxCommitOk, TxRollbackOk, TxSelectOk},
+    transaction::Transaction,
+    SingleVec,
+};
+use tracing::debug;
+
+use crate::{
+    methods::{ack, publish, MethodResponse},
+    Result,
+};
+
+pub fn select(channel: Channel) -> MethodResponse {
+    // a channel can't use publisher confirms and transactions at the same time
+    if channel.confirms.lock().is_some() {
+        return Err(ChannelException::PreconditionFailed.into());
+    }
+
+    channel
+        .transaction
+        .lock()
+        .get_or_insert_with(Transaction::default);
+
+    debug!(channel = %channel.num, "Enabled transactions");
+
+    Ok(Some(Method::TxSelectOk(TxSelectOk)))
+}
+
+/// Routes the published messages and settles the acknowledged messages of the transaction. The
+/// Basic.Return of unroutable mandatory messages are sent before the Tx.CommitOk.
+pub async fn commit(channel: Channel) -> Result<SingleVec<ConnectionEvent>> {
+    let transaction = take_transaction(&channel)?;
+
+    debug!(
+        publishes = %transaction.publishes.len(),
+        settlements = %transaction.settlements.len(),
+        "Committing transaction"
+    );
+
+    // all messages are routed before any is sent, so that a missing exchange doesn't commit only
+    // a part of the transaction
+    let routed = transaction
+        .publishes
+        .into_iter()
+        .map(|message| publish::route(&channel, message))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut events = SingleVec::new();
+
+    for routed in routed {
+        events.extend(publish::send(&channel, routed).await);
+    }
+
+    for (tags, settlement) in transaction.settlements {
+        // the tags were validated when the messages were settled, and only this channel removes them
+        let settled = channel.deliveries.lock().remove_tags(&tags);
+        ack::apply_settlement(&channel, settled, settlement);
+    }
+
+    events.push(ConnectionEvent::Method(
+        channel.num,
+        Box::new(Method::TxCommitOk(TxCommitOk)),
+    ));
+
+    Ok(events)
+}
+
+/// Discards the publishes and acknowledgements of the transaction. Acknowledged messages stay
+/// unacknowledged and are not redelivered.
+pub fn rollback(channel: Channel) -> MethodResponse {
+    let transaction = take_transaction(&channel)?;
+
+    debug!(
+        publishes = %transaction.publishes.len(),
+        settlements = %transaction.settlements.len(),
+        "Rolled back transaction"
+    );
+
+    Ok(Some(Method::TxRollbackOk(TxRollbackOk)))
+}
+
+/// Takes the current transaction and starts a new one
+fn take_transaction(channel: &Channel) -> Result<Transaction> {
+    channel
+        .transaction
+        .lock()
+        .as_mut()
+        .map(std::mem::take)
+        .ok_or_else(|| ChannelException::PreconditionFailed.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use bytes::Bytes;
+    use haesli_core::{
+        connection::{
+            ChannelId, ChannelInner, ChannelNum, ConnectionId, ConnectionInner, ContentHeader,
+        },
+        message::{MessageId, MessageInner, RoutingInformation},
+        methods::{QueueDeclare, Table},
+        queue::{QueueName, PUBLISH_CAPACITY},
+        GlobalData, SingleVec,
+    };
+    use tokio::sync::mpsc;
+
+    use crate::methods::{publish, queue, tx};
+
+    fn message(routing_key: &str) -> Arc<MessageInner> {
+        Arc::new(MessageInner {
+            id: MessageId::random(),
+            header: ContentHeader {
+                class_id: 60,
+                weight: 0,
+                body_size: 1,
+                property_fields: Table::new(),
+            },
+            routing: RoutingInformation {
+                exchange: String::new(),
+                routing_key: routing_key.to_owned(),
+                mandatory: false,
+                immediate: false,
+            },
+            content: SingleVec::from_buf([Bytes::from_static(b"x")]),
+        })
+    }
+
+    #[tokio::test]
+    async fn commit_more_messages_than_queue_capacity() {
+        let global_data = GlobalData::default();
+        let (event_send, _event_recv) = mpsc::channel(10);
+        let (confirm_send, _confirm_recv) = mpsc::unbounded_channel();
+        let connection = ConnectionInner::new(
+            ConnectionId::random(),
+            "127.0.0.1:5672".parse().unwrap(),
+            global_data.clone(),
+            event_send.clone(),
+            confirm_send,
+        );
+        let channel = ChannelInner::new(
+            ChannelId::random(),
+            ChannelNum::new(1),
+            connection,
+            global_data.clone(),
+            event_send,
+        );
+
+        queue::declare(
+            channel.clone(),
+            QueueDeclare {
+                reserved_1: 0,
+                queue: "q".to_owned(),
+                passive: false,
+                durable: false,
+                exclusive: false,
+                auto_delete: false,
+                no_wait: false,
+                arguments: Table::new(),
+            },
+        )
+        .unwrap();
+
+        tx::select(channel.clone()).unwrap();
+
+        let count = PUBLISH_CAPACITY * 2;
+        for _ in 0..count {
+            publish::publish(channel.clone(), message("q"))
+                .await
+                .unwrap();
+        }
+
+        let events = tx::commit(channel).await.unwrap();
+        assert_eq!(events.len(), 1, "only the Tx.CommitOk is sent");
+
+        let queue = global_data.lock().queues[&QueueName::new("q".into())].clone();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while queue.messages.len() < count {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("all messages are queued");
+    }
+}
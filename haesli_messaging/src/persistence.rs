@@ -0,0 +1,573 @@
+//! Keeps the durable exchanges, queues, bindings and persistent messages across restarts of the
+//! broker.
+//!
+//! The whole durable topology is saved every time a part of it changes, which is fine because it
+//! changes rarely compared to the messages flowing through it. It is written by its own thread, so
+//! that nobody has to wait for the disk while holding the lock on the global data.
+//!
+//! Persistent messages are appended to the message log of their durable queue when they are
+//! enqueued, and removed from it once they are acknowledged or dropped. Each message log is written
+//! by a [`LogWriter`].
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::{mpsc, Arc, OnceLock},
+    thread,
+};
+
+use bytes::Bytes;
+use haesli_core::{
+    connection::ContentHeader,
+    error::ConException,
+    exchange::{self, Destination, Exchange, ExchangeName, ExchangeType},
+    message::{Message, MessageId, MessageInner, QueuedMessage, RoutingInformation},
+    methods::{FieldValue, Table},
+    queue::{Queue, QueueDeletion, QueueId, QueueName},
+    GlobalData, GlobalDataInner,
+};
+use haesli_store::{MessageLog, Record, RecordId, Snapshot, Store};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    log_writer::{LogWriter, Written},
+    methods::{exchange::parse_exchange_type, queue::create_queue},
+    routing, Result,
+};
+
+/// Where the durable exchanges, queues, their bindings and persistent messages are saved, if they
+/// are kept across restarts of the broker
+static PERSISTENCE: OnceLock<Persistence> = OnceLock::new();
+
+#[derive(Debug)]
+struct Persistence {
+    store: Store,
+    /// Sends the topology to the thread that saves it
+    topology_sender: mpsc::Sender<Topology>,
+    /// The writers of the message logs of the durable queues
+    log_writers: Mutex<HashMap<QueueId, Arc<LogWriter>>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Topology {
+    exchanges: Vec<DurableExchange>,
+    queues: Vec<DurableQueue>,
+    bindings: Vec<DurableBinding>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DurableExchange {
+    name: String,
+    r#type: String,
+    #[serde(default)]
+    auto_delete: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DurableQueue {
+    name: String,
+    auto_delete: bool,
+    /// The name of the message log of the queue
+    #[serde(default)]
+    log: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DurableBinding {
+    source: String,
+    destination: DurableDestination,
+    routing_key: String,
+    arguments: Table,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum DurableDestination {
+    Queue(String),
+    Exchange(String),
+}
+
+/// A message as it is stored in the message log of a queue
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedMessage {
+    exchange: String,
+    routing_key: String,
+    class_id: u16,
+    weight: u16,
+    properties: Table,
+    content: Vec<u8>,
+}
+
+/// Restores the topology and the persistent messages from the store, and keeps saving them there
+/// from now on
+pub fn restore(global_data: &GlobalData, store: Store) -> io::Result<()> {
+    let topology = store.topology().load::<Topology>()?.unwrap_or_default();
+
+    info!(
+        path = %store.dir().display(),
+        exchanges = %topology.exchanges.len(),
+        queues = %topology.queues.len(),
+        bindings = %topology.bindings.len(),
+        "Restoring durable topology"
+    );
+
+    let (topology_sender, topology_receiver) = mpsc::channel();
+    let snapshot = Snapshot::new(store.topology().path());
+
+    thread::Builder::new()
+        .name("topology-writer".to_owned())
+        .spawn(move || write_topology(&snapshot, &topology_receiver))?;
+
+    let persistence = Persistence {
+        store,
+        topology_sender,
+        log_writers: Mutex::default(),
+    };
+    if PERSISTENCE.set(persistence).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the data has already been restored",
+        ));
+    }
+
+    // the restored queues already write to their message logs
+    let store = &PERSISTENCE.get().expect("the store was just set").store;
+
+    {
+        let mut global_data = global_data.lock();
+
+        for DurableExchange {
+            name,
+            r#type,
+            auto_delete,
+        } in topology.exchanges
+        {
+            let Some(kind) = parse_exchange_type(&r#type) else {
+                warn!(%name, %r#type, "Skipping durable exchange with unknown type");
+                continue;
+            };
+
+            let name = ExchangeName::new(name.into());
+            global_data.exchanges.insert(
+                name.clone(),
+                Exchange {
+                    name,
+                    kind,
+                    durable: true,
+                    auto_delete,
+                },
+            );
+        }
+    }
+
+    let mut used_logs = HashSet::new();
+
+    for DurableQueue {
+        name,
+        auto_delete,
+        log,
+    } in topology.queues
+    {
+        let name = QueueName::new(name.into());
+
+        let (log, records) = match &log {
+            Some(log_name) => store.open_message_log(log_name)?,
+            // the queue was saved before its messages were persisted
+            None => (store.create_message_log(&new_log_name())?, Vec::new()),
+        };
+        used_logs.insert(log_name(&log).to_owned());
+
+        let queue = match create_queue(
+            global_data,
+            &mut global_data.lock(),
+            name.clone(),
+            true,
+            None,
+            auto_delete,
+            Some(log),
+        ) {
+            Ok(queue) => queue,
+            Err(err) => {
+                warn!(%name, ?err, "Failed to restore durable queue");
+                continue;
+            }
+        };
+
+        let message_count = records.len();
+
+        for Record { id, payload } in records {
+            match decode_message(&payload) {
+                // we don't know whether the message was delivered before the restart
+                Some(message) => queue.messages.append(QueuedMessage {
+                    message,
+                    redelivered: true,
+                    record: Some(id),
+                }),
+                None => {
+                    warn!(%name, record = %id, "Skipping persistent message that can't be decoded")
+                }
+            }
+        }
+
+        info!(%name, %message_count, "Restored durable queue");
+    }
+
+    // the logs of queues that were deleted just before the broker stopped
+    for name in store.message_logs()? {
+        if !used_logs.contains(&name) {
+            info!(%name, "Deleting message log of deleted queue");
+            store.delete_message_log(&name)?;
+        }
+    }
+
+    let mut global_data = global_data.lock();
+
+    for binding in topology.bindings {
+        let destination = match &binding.destination {
+            DurableDestination::Queue(name) => global_data
+                .queues
+                .get(name.as_str())
+                .map(|queue| Destination::Queue(queue.clone())),
+            DurableDestination::Exchange(name) => global_data
+                .exchanges
+                .get_key_value(name.as_str())
+                .map(|(name, _)| Destination::Exchange(name.clone())),
+        };
+
+        let restored = match (
+            destination,
+            global_data.exchanges.get_mut(binding.source.as_str()),
+        ) {
+            (Some(destination), Some(source)) => {
+                routing::bind(source, binding.routing_key, binding.arguments, destination).is_ok()
+            }
+            _ => false,
+        };
+
+        if !restored {
+            warn!(source = %binding.source, destination = ?binding.destination, "Failed to restore durable binding");
+        }
+    }
+
+    // the restored topology can differ from the saved one, for example because of new message logs
+    save_topology(&global_data);
+
+    Ok(())
+}
+
+/// Whether a binding from the exchange to the destination is part of the durable topology
+pub fn is_durable_binding(
+    global_data: &GlobalDataInner,
+    exchange: &str,
+    destination: &Destination,
+) -> bool {
+    let is_durable_exchange = |name: &str| {
+        global_data
+            .exchanges
+            .get(name)
+            .is_some_and(|exchange| exchange.durable)
+    };
+
+    let is_durable_destination = match destination {
+        Destination::Queue(queue) => queue.durable && queue.exclusive.is_none(),
+        Destination::Exchange(name) => is_durable_exchange(name),
+    };
+
+    // the bindings of the default exchange are implied by the queues
+    !exchange.is_empty() && is_durable_exchange(exchange) && is_durable_destination
+}
+
+/// Saves the durable topology, if the broker keeps it across restarts. Only a copy of the topology
+/// is taken right away, it is written to the disk by the topology writer thread afterwards.
+pub fn save_topology(global_data: &GlobalDataInner) {
+    let Some(persistence) = PERSISTENCE.get() else {
+        return;
+    };
+
+    let topology = durable_topology(global_data, persistence);
+
+    if persistence.topology_sender.send(topology).is_err() {
+        error!("Topology writer has stopped, failed to save durable topology");
+    }
+}
+
+/// Saves the topologies sent to the thread, until the broker stops.
+///
+/// Failing to save one is only logged, the broker keeps working with the topology it has in memory.
+fn write_topology(snapshot: &Snapshot, topology_receiver: &mpsc::Receiver<Topology>) {
+    while let Ok(topology) = topology_receiver.recv() {
+        // the topologies that were sent while the last one was saved are outdated already
+        let topology = topology_receiver.try_iter().last().unwrap_or(topology);
+
+        if let Err(err) = snapshot.save(&topology) {
+            error!(?err, path = %snapshot.path().display(), "Failed to save durable topology");
+        }
+    }
+}
+
+fn durable_topology(global_data: &GlobalDataInner, persistence: &Persistence) -> Topology {
+    let log_writers = persistence.log_writers.lock();
+
+    let exchanges = global_data
+        .exchanges
+        .values()
+        // the predeclared exchanges are created again by the broker itself
+        .filter(|exchange| exchange.durable && !exchange::is_predeclared(&exchange.name))
+        .filter_map(|exchange| {
+            Some(DurableExchange {
+                name: exchange.name.to_string(),
+                r#type: exchange_type_name(exchange)?.to_owned(),
+                auto_delete: exchange.auto_delete,
+            })
+        })
+        .collect();
+
+    let queues = global_data
+        .queues
+        .values()
+        // exclusive queues belong to a connection, which doesn't survive the restart either
+        .filter(|queue| queue.durable && queue.exclusive.is_none())
+        .map(|queue| DurableQueue {
+            name: queue.name.to_string(),
+            auto_delete: matches!(queue.deletion, QueueDeletion::Auto),
+            log: log_writers
+                .get(&queue.id)
+                .map(|log_writer| log_writer.name().to_owned()),
+        })
+        .collect();
+
+    let bindings = global_data
+        .exchanges
+        .values()
+        .flat_map(|exchange| {
+            routing::bindings(exchange)
+                .into_iter()
+                .filter(|(_, _, destination)| {
+                    is_durable_binding(global_data, &exchange.name, destination)
+                })
+                .map(|(routing_key, arguments, destination)| DurableBinding {
+                    source: exchange.name.to_string(),
+                    destination: match destination {
+                        Destination::Queue(queue) => {
+                            DurableDestination::Queue(queue.name.to_string())
+                        }
+                        Destination::Exchange(name) => {
+                            DurableDestination::Exchange(name.to_string())
+                        }
+                    },
+                    routing_key,
+                    arguments,
+                })
+        })
+        .collect();
+
+    Topology {
+        exchanges,
+        queues,
+        bindings,
+    }
+}
+
+fn exchange_type_name(exchange: &Exchange) -> Option<&'static str> {
+    match exchange.kind {
+        ExchangeType::Direct { .. } => Some("direct"),
+        ExchangeType::Fanout { .. } => Some("fanout"),
+        ExchangeType::Topic { .. } => Some("topic"),
+        ExchangeType::Headers { .. } => Some("headers"),
+        ExchangeType::System => None,
+    }
+}
+
+fn new_log_name() -> String {
+    haesli_core::random_uuid().to_string()
+}
+
+fn log_name(log: &MessageLog) -> &str {
+    log.dir()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .expect("message logs are created with a name")
+}
+
+/// Creates the message log for a new durable queue, if the broker keeps messages across restarts
+pub(crate) fn create_message_log() -> Result<Option<MessageLog>> {
+    let Some(persistence) = PERSISTENCE.get() else {
+        return Ok(None);
+    };
+
+    match persistence.store.create_message_log(&new_log_name()) {
+        Ok(log) => Ok(Some(log)),
+        Err(err) => {
+            error!(?err, "Failed to create message log");
+            Err(ConException::InternalError.into())
+        }
+    }
+}
+
+/// Deletes a message log that was created for a queue that turned out to exist already
+pub(crate) fn delete_unused_message_log(log: MessageLog) {
+    let dir = log.dir().to_owned();
+    if let Err(err) = log.delete() {
+        error!(?err, log = %dir.display(), "Failed to delete unused message log");
+    }
+}
+
+/// Starts writing the message log of a new durable queue
+pub(crate) fn start_log_writer(queue: &Queue, log: MessageLog) {
+    let Some(persistence) = PERSISTENCE.get() else {
+        return;
+    };
+
+    let log_writer = LogWriter::start(log_name(&log).to_owned(), log);
+
+    persistence
+        .log_writers
+        .lock()
+        .insert(queue.id, Arc::new(log_writer));
+}
+
+/// Deletes the message log of a deleted queue
+pub(crate) fn delete_message_log(queue: &Queue) {
+    let log_writer = PERSISTENCE
+        .get()
+        .and_then(|persistence| persistence.log_writers.lock().remove(&queue.id));
+
+    if let Some(log_writer) = log_writer {
+        log_writer.delete();
+    }
+}
+
+fn log_writer(queue: &Queue) -> Option<Arc<LogWriter>> {
+    let persistence = PERSISTENCE.get()?;
+    let log_writers = persistence.log_writers.lock();
+    log_writers.get(&queue.id).cloned()
+}
+
+/// Whether the publisher wants the message to survive a restart of the broker
+fn is_persistent(message: &Message) -> bool {
+    matches!(
+        message.header.property_fields.get("delivery-mode"),
+        Some(FieldValue::ShortShortUInt(2))
+    )
+}
+
+/// Appends the message to the message log of the queue, if it is persistent and the queue is
+/// durable. Returns the record of the message, together with a [`Written`] that tells when the
+/// message is on the disk.
+pub(crate) async fn persist_message(
+    queue: &Queue,
+    message: &Message,
+) -> io::Result<Option<(RecordId, Written)>> {
+    if !is_persistent(message) {
+        return Ok(None);
+    }
+
+    let Some(log_writer) = log_writer(queue) else {
+        return Ok(None);
+    };
+
+    let payload =
+        encode_message(message).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    Ok(Some(log_writer.append(payload).await))
+}
+
+/// Removes a message from the message log of the queue, because it has been acknowledged or
+/// dropped. Messages that are not persistent are ignored.
+pub(crate) fn remove_message(queue: &Queue, record: Option<RecordId>) {
+    let Some(record) = record else {
+        return;
+    };
+
+    if let Some(log_writer) = log_writer(queue) {
+        log_writer.remove(record);
+    }
+}
+
+fn encode_message(message: &Message) -> bincode::Result<Vec<u8>> {
+    bincode::serialize(&PersistedMessage {
+        exchange: message.routing.exchange.clone(),
+        routing_key: message.routing.routing_key.clone(),
+        class_id: message.header.class_id,
+        weight: message.header.weight,
+        properties: message.header.property_fields.clone(),
+        content: message.content.concat(),
+    })
+}
+
+fn decode_message(payload: &[u8]) -> Option<Message> {
+    let persisted = bincode::deserialize::<PersistedMessage>(payload).ok()?;
+
+    Some(Arc::new(MessageInner {
+        id: MessageId::random(),
+        header: ContentHeader {
+            class_id: persisted.class_id,
+            weight: persisted.weight,
+            body_size: persisted.content.len() as u64,
+            property_fields: persisted.properties,
+        },
+        routing: RoutingInformation {
+            exchange: persisted.exchange,
+            routing_key: persisted.routing_key,
+            mandatory: false,
+            immediate: false,
+        },
+        content: std::iter::once(Bytes::from(persisted.content)).collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use haesli_core::{
+        connection::ContentHeader,
+        message::{MessageId, MessageInner, RoutingInformation},
+        methods::{FieldValue, Table},
+    };
+
+    use super::{decode_message, encode_message, is_persistent};
+
+    #[test]
+    fn decode_encoded_message() {
+        let properties = Table::from([
+            ("delivery-mode".to_owned(), FieldValue::ShortShortUInt(2)),
+            (
+                "headers".to_owned(),
+                FieldValue::FieldTable(Table::from([(
+                    "key".to_owned(),
+                    FieldValue::LongString(b"value".to_vec()),
+                )])),
+            ),
+        ]);
+
+        let message = Arc::new(MessageInner {
+            id: MessageId::random(),
+            header: ContentHeader {
+                class_id: 60,
+                weight: 0,
+                body_size: 11,
+                property_fields: properties,
+            },
+            routing: RoutingInformation {
+                exchange: "amqp.direct".to_owned(),
+                routing_key: "key".to_owned(),
+                mandatory: true,
+                immediate: false,
+            },
+            content: [Bytes::from_static(b"hello "), Bytes::from_static(b"world")]
+                .into_iter()
+                .collect(),
+        });
+        assert!(is_persistent(&message));
+
+        let decoded = decode_message(&encode_message(&message).unwrap()).unwrap();
+
+        assert_eq!(decoded.header, message.header);
+        assert_eq!(decoded.routing.exchange, "amqp.direct");
+        assert_eq!(decoded.routing.routing_key, "key");
+        assert_eq!(decoded.content.concat(), b"hello world");
+    }
+}
@@ -1,21 +1,66 @@
-use std::borrow::Borrow;
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashSet, VecDeque},
+    ops::Bound,
+    sync::Arc,
+};
 
 use haesli_core::{
-    connection::ConnectionEvent,
-    consumer::Consumer,
-    message::Message,
+    confirm::PendingConfirm,
+    connection::{Connection, ConnectionEvent, ConnectionId},
+    consumer::{Consumer, ConsumerId},
+    delivery::{Deliveries, Unacked},
+    message::{Message, QueuedMessage},
     methods::{BasicDeliver, Method},
-    queue::{Queue, QueueEvent, QueueEventReceiver},
+    queue::{
+        Queue, QueueDeletion, QueueEvent, QueueEventReceiver, QueuePublish, QueuePublishReceiver,
+    },
     GlobalData,
 };
-use tracing::info;
+use parking_lot::Mutex;
+use tokio::{
+    select,
+    sync::{
+        mpsc::{error::TrySendError, Permit},
+        oneshot::error::TryRecvError,
+    },
+};
+use tracing::{error, info};
+
+use crate::{log_writer::Written, methods::queue, persistence};
+
+/// Why a message couldn't be delivered to a consumer
+enum DeliveryError {
+    /// The connection of the consumer can't take any more events right now
+    FlowControl,
+    /// The consumer has reached its prefetch limit
+    Prefetch,
+    /// The connection of the consumer is closed
+    Closed,
+}
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct QueueTask {
     global_data: GlobalData,
     event_recv: QueueEventReceiver,
+    publish_recv: QueuePublishReceiver,
     queue: Queue,
+    /// The consumer that got the last message, the next one is tried first
+    last_consumer: Option<ConsumerId>,
+    /// The connections that were too busy to take a message, a task waits for each of them
+    waiting_for_capacity: Arc<Mutex<HashSet<ConnectionId>>>,
+    /// Published messages that wait until the persistent messages among them are on the disk,
+    /// in the order they were published
+    persisting: VecDeque<PersistingMessage>,
+}
+
+#[derive(Debug)]
+struct PersistingMessage {
+    message: QueuedMessage,
+    confirm: Option<PendingConfirm>,
+    /// `None` if the message doesn't have to be written and only waits behind the others
+    written: Option<Written>,
 }
 
 impl QueueTask {
@@ -23,11 +68,20 @@ impl QueueTask {
         self.queue.name.borrow()
     }
 
-    pub fn new(global_data: GlobalData, event_recv: QueueEventReceiver, queue: Queue) -> Self {
+    pub fn new(
+        global_data: GlobalData,
+        event_recv: QueueEventReceiver,
+        publish_recv: QueuePublishReceiver,
+        queue: Queue,
+    ) -> Self {
         Self {
             global_data,
             event_recv,
+            publish_recv,
             queue,
+            last_consumer: None,
+            waiting_for_capacity: Arc::default(),
+            persisting: VecDeque::new(),
         }
     }
 
@@ -36,12 +90,30 @@ impl QueueTask {
         info!("Started queue worker task");
 
         loop {
-            let next_event = self.event_recv.recv().await;
+            // events are handled first, so that a deleted queue doesn't take all published messages first
+            let next_event = select! {
+                biased;
+                event = self.event_recv.recv() => event,
+                written = first_written(&mut self.persisting), if !self.persisting.is_empty() => {
+                    self.handle_written(written).await;
+                    continue;
+                }
+                publish = self.publish_recv.recv() => match publish {
+                    Some(QueuePublish { message, confirm }) => {
+                        self.handle_publish_message(message, confirm).await;
+                        continue;
+                    }
+                    None => None,
+                },
+            };
 
             match next_event {
-                Some(QueueEvent::PublishMessage(message)) => {
-                    self.handle_publish_message(message).await
-                }
+                Some(
+                    QueueEvent::MessagesRequeued
+                    | QueueEvent::CapacityAvailable
+                    | QueueEvent::ConsumerAdded,
+                ) => self.deliver_queued().await,
+                Some(QueueEvent::ConsumerRemoved) => self.handle_consumer_removed(),
                 Some(QueueEvent::Shutdown) | None => {
                     self.cleanup().await;
                     return;
@@ -50,52 +122,243 @@ impl QueueTask {
         }
     }
 
-    #[tracing::instrument(skip(self), fields(name = self.show_name()), level = "debug")]
-    async fn handle_publish_message(&mut self, message: Message) {
-        // todo: we just send it to the consumer directly and ignore it if the consumer doesn't exist
-        // consuming is hard, but this should work *for now*
+    #[tracing::instrument(skip(self, confirm), fields(name = self.show_name()), level = "debug")]
+    async fn handle_publish_message(&mut self, message: Message, confirm: Option<PendingConfirm>) {
+        let (record, written) = match persistence::persist_message(&self.queue, &message).await {
+            Ok(Some((record, written))) => (Some(record), Some(written)),
+            Ok(None) => (None, None),
+            Err(err) => {
+                // the message is dropped and dropping the confirm sends a Basic.Nack, so the
+                // publisher can send it again
+                error!(?err, "Failed to persist message");
+                return;
+            }
+        };
+
+        let message = QueuedMessage {
+            message,
+            redelivered: false,
+            record,
+        };
 
-        let could_deliver = {
-            let consumers = self.queue.consumers.lock();
-            if let Some(consumer) = consumers.values().next() {
-                Self::try_deliver(&message, consumer)
+        if written.is_some() || !self.persisting.is_empty() {
+            // the message has to wait behind the messages that are not on the disk yet
+            self.persisting.push_back(PersistingMessage {
+                message,
+                confirm,
+                written,
+            });
+            return;
+        }
+
+        self.queue_message(message).await;
+
+        if let Some(confirm) = confirm {
+            confirm.confirm();
+        }
+
+        self.deliver_queued().await;
+    }
+
+    /// Queues the first persisting message now that it has been written, and the messages behind
+    /// it that don't have to wait anymore.
+    async fn handle_written(&mut self, mut written: bool) {
+        while let Some(persisting) = self.persisting.pop_front() {
+            if written {
+                self.queue_message(persisting.message).await;
+
+                if let Some(confirm) = persisting.confirm {
+                    confirm.confirm();
+                }
             } else {
-                Err(())
+                // the message is not in the message log, so it is dropped like if persisting it
+                // had failed right away
+                error!("Failed to write message to message log");
             }
-        };
 
-        if let Err(()) = could_deliver {
-            self.queue_message(message).await;
+            written = match self.persisting.front_mut().map(|next| &mut next.written) {
+                Some(None) => true,
+                Some(Some(next)) => match next.try_recv() {
+                    Ok(()) => true,
+                    Err(TryRecvError::Closed) => false,
+                    Err(TryRecvError::Empty) => break,
+                },
+                None => break,
+            };
+        }
+
+        self.deliver_queued().await;
+    }
+
+    async fn deliver_queued(&mut self) {
+        while let Some(message) = self.queue.messages.try_get() {
+            let result = {
+                let consumers = self.queue.consumers.lock();
+                let mut blocked = Vec::new();
+
+                // consumers that are blocked by flow control or their prefetch limit are skipped
+                let delivered_to = self.round_robin(&consumers).find_map(|consumer| {
+                    match self.try_deliver(&message, consumer) {
+                        Ok(()) => Some(consumer.id),
+                        Err(DeliveryError::FlowControl) => {
+                            blocked.push(consumer.channel.connection.clone());
+                            None
+                        }
+                        Err(DeliveryError::Prefetch | DeliveryError::Closed) => None,
+                    }
+                });
+
+                delivered_to.ok_or(blocked)
+            };
+
+            match result {
+                Ok(consumer) => self.last_consumer = Some(consumer),
+                Err(blocked) => {
+                    self.queue.messages.prepend(message);
+                    blocked
+                        .iter()
+                        .for_each(|connection| self.wait_for_capacity(connection));
+                    return;
+                }
+            }
         }
     }
 
-    #[tracing::instrument(skip(consumer), level = "trace")]
-    fn try_deliver(message: &Message, consumer: &Consumer) -> Result<(), ()> {
-        let routing = &message.routing;
+    /// Sends a `CapacityAvailable` event to the queue once the connection can take events again.
+    ///
+    /// The worker doesn't wait for the connection itself, since it has to keep handling the other
+    /// events of the queue meanwhile, and a client that doesn't read its messages would block it.
+    fn wait_for_capacity(&self, connection: &Connection) {
+        if !self.waiting_for_capacity.lock().insert(connection.id) {
+            return;
+        }
+
+        let id = connection.id;
+        let event_sender = connection.event_sender.clone();
+        let waiting_for_capacity = self.waiting_for_capacity.clone();
+        let queue = self.queue.clone();
+
+        tokio::spawn(async move {
+            // the permit is given back right away, the worker reserves its own when delivering
+            let _ = event_sender.reserve().await;
+            waiting_for_capacity.lock().remove(&id);
+            let _ = queue.event_send.send(QueueEvent::CapacityAvailable);
+        });
+    }
+
+    /// Returns the consumers in the order they should be tried, starting after the consumer that
+    /// got the last message.
+    fn round_robin<'a>(
+        &self,
+        consumers: &'a BTreeMap<ConsumerId, Consumer>,
+    ) -> impl Iterator<Item = &'a Consumer> {
+        let last = self.last_consumer;
+        let after_last = consumers
+            .range((
+                last.map_or(Bound::Unbounded, Bound::Excluded),
+                Bound::Unbounded,
+            ))
+            .map(|(_, consumer)| consumer);
+        let up_to_last = consumers
+            .values()
+            .take_while(move |consumer| Some(consumer.id) <= last);
+
+        after_last.chain(up_to_last)
+    }
+
+    fn try_deliver(
+        &self,
+        message: &QueuedMessage,
+        consumer: &Consumer,
+    ) -> Result<(), DeliveryError> {
+        let permit = consumer
+            .channel
+            .event_sender
+            .try_reserve()
+            .map_err(|err| match err {
+                TrySendError::Full(()) => DeliveryError::FlowControl,
+                TrySendError::Closed(()) => DeliveryError::Closed,
+            })?;
+
+        self.deliver(message, consumer, permit)
+    }
+
+    #[tracing::instrument(skip(self, consumer, permit), level = "trace")]
+    fn deliver(
+        &self,
+        message: &QueuedMessage,
+        consumer: &Consumer,
+        permit: Permit<'_, ConnectionEvent>,
+    ) -> Result<(), DeliveryError> {
+        let routing = &message.message.routing;
+
+        let channel_prefetch = consumer.channel.prefetch.lock().channel;
+
+        // the lock is held until the message is tracked, so that it can't be acked before that
+        let mut deliveries = consumer.channel.deliveries.lock();
+
+        if !consumer.no_ack && !has_capacity(&deliveries, consumer, channel_prefetch) {
+            return Err(DeliveryError::Prefetch);
+        }
+
+        let delivery_tag = deliveries.next_tag();
 
         let method = Box::new(Method::BasicDeliver(BasicDeliver {
             consumer_tag: consumer.tag.clone(),
-            delivery_tag: 0,
-            redelivered: false,
+            delivery_tag,
+            redelivered: message.redelivered,
             exchange: routing.exchange.clone(),
             routing_key: routing.routing_key.clone(),
         }));
 
-        let result = consumer
-            .channel
-            .event_sender
-            .try_send(ConnectionEvent::MethodContent(
-                consumer.channel.num,
-                method,
-                message.header.clone(),
-                message.content.clone(),
-            ));
-
-        result.map_err(drop)
+        permit.send(ConnectionEvent::MethodContent(
+            consumer.channel.num,
+            method,
+            message.message.header.clone(),
+            message.message.content.clone(),
+        ));
+
+        if consumer.no_ack {
+            persistence::remove_message(&self.queue, message.record);
+        } else {
+            deliveries.track(
+                delivery_tag,
+                Unacked {
+                    message: message.message.clone(),
+                    queue: self.queue.clone(),
+                    consumer: Some(consumer.id),
+                    record: message.record,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    fn handle_consumer_removed(&mut self) {
+        let is_auto_delete = matches!(self.queue.deletion, QueueDeletion::Auto);
+
+        if !is_auto_delete {
+            return;
+        }
+
+        let removed = {
+            let mut global_data = self.global_data.lock();
+            // consumers are added under the global lock, so checking them here means that no
+            // consumer can start consuming from the queue while it is deleted
+            self.queue.consumers.lock().is_empty()
+                && queue::remove_queue(&mut global_data, &self.queue)
+        };
+
+        if removed {
+            // the queue task is shut down by the deletion as well
+            let message_count = queue::shut_down_queue(&self.queue);
+            info!(%message_count, "Deleted auto-delete queue without consumers");
+        }
     }
 
     #[tracing::instrument(skip(self), fields(name = self.show_name()), level = "trace")]
-    async fn queue_message(&mut self, message: Message) {
+    async fn queue_message(&mut self, message: QueuedMessage) {
         self.queue.messages.append(message);
     }
 
@@ -103,3 +366,25 @@ impl QueueTask {
         // do stuff or something like that id whatever
     }
 }
+
+/// Waits until the first persisting message has been written, returns whether writing it worked
+async fn first_written(persisting: &mut VecDeque<PersistingMessage>) -> bool {
+    match persisting
+        .front_mut()
+        .and_then(|first| first.written.as_mut())
+    {
+        Some(written) => written.await.is_ok(),
+        None => true,
+    }
+}
+
+/// Whether the consumer can take another message without exceeding its prefetch limits
+fn has_capacity(deliveries: &Deliveries, consumer: &Consumer, channel_prefetch: u16) -> bool {
+    let below_limit = |limit: u16, unacked: usize| limit == 0 || unacked < usize::from(limit);
+
+    below_limit(channel_prefetch, deliveries.unacked_len())
+        && below_limit(
+            consumer.prefetch_count,
+            deliveries.consumer_unacked_len(consumer.id),
+        )
+}
@@ -1,166 +1,615 @@
+use std::collections::{HashMap, HashSet};
+
 use haesli_core::{
-    exchange::{Exchange, ExchangeType, TopicSegment},
+    amqp_todo,
+    error::ChannelException,
+    exchange::{Destination, Exchange, ExchangeName, ExchangeType, HeadersBinding, HeadersMatch},
+    methods::{FieldValue, Table},
     queue::Queue,
 };
 
-fn parse_topic(topic: &str) -> Vec<TopicSegment> {
-    topic
-        .split('.')
-        .map(|segment| match segment {
-            "*" => TopicSegment::SingleWildcard,
-            "#" => TopicSegment::MultiWildcard,
-            word => TopicSegment::Word(word.to_owned()),
-        })
-        .collect()
+use crate::Result;
+
+/// Parses the binding arguments of a headers exchange binding
+fn parse_headers_binding(arguments: Table) -> Result<HeadersBinding> {
+    let x_match = match arguments.get("x-match") {
+        None => HeadersMatch::All,
+        Some(value) => match string_value(value) {
+            Some(b"all") => HeadersMatch::All,
+            Some(b"any") => HeadersMatch::Any,
+            _ => return Err(ChannelException::PreconditionFailed.into()),
+        },
+    };
+
+    // arguments starting with `x-` are not matched against the message headers
+    let headers = arguments
+        .into_iter()
+        .filter(|(name, _)| !name.starts_with("x-"))
+        .collect();
+
+    Ok(HeadersBinding { x_match, headers })
 }
 
-pub fn bind(exchange: &mut Exchange, routing_key: String, queue: Queue) {
+fn string_value(value: &FieldValue) -> Option<&[u8]> {
+    match value {
+        FieldValue::ShortString(str) => Some(str.as_bytes()),
+        FieldValue::LongString(str) => Some(str),
+        _ => None,
+    }
+}
+
+/// Binds the destination to the exchange. Binding the same destination with the same routing key
+/// again has no effect. The arguments are only used by headers exchanges.
+pub fn bind(
+    exchange: &mut Exchange,
+    routing_key: String,
+    arguments: Table,
+    destination: Destination,
+) -> Result<()> {
+    if let ExchangeType::Headers { bindings } = &mut exchange.kind {
+        let binding = parse_headers_binding(arguments)?;
+        if !bindings
+            .iter()
+            .any(|(bound_binding, bound)| *bound_binding == binding && bound.is_same(&destination))
+        {
+            bindings.push((binding, destination));
+        }
+        return Ok(());
+    }
+
+    if !arguments.is_empty() {
+        amqp_todo!();
+    }
+
     match &mut exchange.kind {
         ExchangeType::Direct { bindings } => {
-            bindings.insert(routing_key, queue);
+            let bound = bindings.entry(routing_key).or_default();
+            if !bound.iter().any(|bound| bound.is_same(&destination)) {
+                bound.push(destination);
+            }
         }
-        ExchangeType::Fanout { bindings } => bindings.push(queue),
-        ExchangeType::Topic { bindings } => bindings.push((parse_topic(&routing_key), queue)),
-        ExchangeType::Headers => {} // unsupported
-        ExchangeType::System => {}  // unsupported
+        ExchangeType::Fanout { bindings } => {
+            // the routing key is ignored, so a destination only needs to be bound once
+            if !bindings.iter().any(|bound| bound.is_same(&destination)) {
+                bindings.push(destination);
+            }
+        }
+        ExchangeType::Topic { bindings } => {
+            let bound = bindings.entry(&routing_key);
+            if !bound.iter().any(|bound| bound.is_same(&destination)) {
+                bound.push(destination);
+            }
+        }
+        ExchangeType::Headers { .. } => unreachable!("headers exchanges are handled above"),
+        ExchangeType::System => {} // unsupported
     }
+
+    Ok(())
 }
 
-/// Route a message to a queue. Returns the queues to send it to, or `None` if it can't be matched
-pub fn route_message(exchange: &Exchange, routing_key: &str) -> Option<Vec<Queue>> {
-    match &exchange.kind {
+/// Removes the binding of the destination with the routing key and arguments from the exchange,
+/// if it exists
+pub fn unbind(
+    exchange: &mut Exchange,
+    routing_key: &str,
+    arguments: Table,
+    destination: &Destination,
+) {
+    match &mut exchange.kind {
         ExchangeType::Direct { bindings } => {
-            // 3.1.3.1 - routing-key = routing-key
-            bindings.get(routing_key).cloned().map(|q| vec![q])
+            if let Some(bound) = bindings.get_mut(routing_key) {
+                bound.retain(|bound| !bound.is_same(destination));
+                if bound.is_empty() {
+                    bindings.remove(routing_key);
+                }
+            }
         }
         ExchangeType::Fanout { bindings } => {
-            // 3.1.3.2 - unconditionally
-            Some(bindings.clone()) // see, this is actually Not That Bad I Hope
+            bindings.retain(|bound| !bound.is_same(destination));
         }
         ExchangeType::Topic { bindings } => {
-            // todo: optimizing this is a fun problem
-
-            Some(match_topic(bindings, routing_key))
+            bindings.retain_pattern(routing_key, |bound| !bound.is_same(destination));
         }
-        ExchangeType::Headers => None, // unsupported
-        ExchangeType::System => None,  // unsupported
+        ExchangeType::Headers { bindings } => {
+            // arguments that can't be parsed can't belong to an existing binding
+            if let Ok(binding) = parse_headers_binding(arguments) {
+                bindings.retain(|(bound_binding, bound)| {
+                    *bound_binding != binding || !bound.is_same(destination)
+                });
+            }
+        }
+        ExchangeType::System => {} // unsupported
     }
 }
 
-fn match_topic<Q: Clone>(patterns: &[(Vec<TopicSegment>, Q)], routing_key: &str) -> Vec<Q> {
-    patterns
-        .iter()
-        .filter_map(|(pattern, value)| {
-            let mut key_segments = routing_key.split('.');
-            let mut pat_segments = pattern.iter();
-
-            loop {
-                let key = key_segments.next();
-                let pat = pat_segments.next();
-
-                match (pat, key) {
-                    (Some(TopicSegment::Word(pat)), Some(key)) if pat != key => return None,
-                    (Some(TopicSegment::Word(_)), Some(_)) => {}
-                    (Some(TopicSegment::SingleWildcard), Some(_)) => {}
-                    (Some(TopicSegment::MultiWildcard), _) => {
-                        let pat = pat_segments.next();
-                        match pat {
-                            Some(pat) => {
-                                // loop until we find the next pat segment in the key
-                                loop {
-                                    let key = key_segments.next();
-                                    match (pat, key) {
-                                        (_, None) => return None, // we are expecting something after the wildcard
-                                        (TopicSegment::Word(pat), Some(key)) if pat == key => {
-                                            break; // we matched all of the `#` and the segment afterwards
-                                        }
-                                        (TopicSegment::SingleWildcard, Some(_)) => {
-                                            break; // `#.*` is a cursed pattern, match `#` for 1
-                                        }
-                                        (TopicSegment::MultiWildcard, Some(_)) => {
-                                            break; // at this point I don't even care, who the fuck
-                                                   // would use `#.#`, just only match 2 which is
-                                                   // wrong so todo I guess lol
-                                        }
-                                        _ => continue,
-                                    }
-                                }
-                            }
-                            None => break, // pattern ends with `#`, it certainly matches
-                        }
-                    }
-                    (Some(_), None) => return None,
-                    (None, Some(_)) => return None,
-                    (None, None) => break,
+/// Removes all bindings of the destination from the exchange
+pub fn unbind_all(exchange: &mut Exchange, destination: &Destination) {
+    let is_other = |bound: &Destination| !bound.is_same(destination);
+
+    match &mut exchange.kind {
+        ExchangeType::Direct { bindings } => bindings.retain(|_, bound| {
+            bound.retain(is_other);
+            !bound.is_empty()
+        }),
+        ExchangeType::Fanout { bindings } => bindings.retain(is_other),
+        ExchangeType::Topic { bindings } => bindings.retain(is_other),
+        ExchangeType::Headers { bindings } => bindings.retain(|(_, bound)| is_other(bound)),
+        ExchangeType::System => {} // unsupported
+    }
+}
+
+/// Returns the routing key, arguments and destination of every binding of the exchange, which
+/// can be passed to [`bind`] again to restore it
+pub fn bindings(exchange: &Exchange) -> Vec<(String, Table, &Destination)> {
+    match &exchange.kind {
+        ExchangeType::Direct { bindings } => bindings
+            .iter()
+            .flat_map(|(routing_key, bound)| {
+                bound
+                    .iter()
+                    .map(|destination| (routing_key.clone(), Table::new(), destination))
+            })
+            .collect(),
+        ExchangeType::Fanout { bindings } => bindings
+            .iter()
+            .map(|destination| (String::new(), Table::new(), destination))
+            .collect(),
+        ExchangeType::Topic { bindings } => bindings
+            .bindings()
+            .into_iter()
+            .map(|(pattern, destination)| (pattern, Table::new(), destination))
+            .collect(),
+        ExchangeType::Headers { bindings } => bindings
+            .iter()
+            .map(|(binding, destination)| {
+                let x_match = match binding.x_match {
+                    HeadersMatch::All => "all",
+                    HeadersMatch::Any => "any",
+                };
+                let mut arguments = binding.headers.clone();
+                arguments.insert("x-match".to_owned(), FieldValue::LongString(x_match.into()));
+                (String::new(), arguments, destination)
+            })
+            .collect(),
+        ExchangeType::System => Vec::new(), // unsupported
+    }
+}
+
+/// Whether nothing is bound to the exchange
+pub fn is_unused(exchange: &Exchange) -> bool {
+    match &exchange.kind {
+        ExchangeType::Direct { bindings } => bindings.is_empty(),
+        ExchangeType::Fanout { bindings } => bindings.is_empty(),
+        ExchangeType::Topic { bindings } => bindings.is_empty(),
+        ExchangeType::Headers { bindings } => bindings.is_empty(),
+        ExchangeType::System => true, // unsupported
+    }
+}
+
+/// Route a message to a queue. Returns the queues to send it to, or `None` if it can't be matched.
+///
+/// Exchanges that are bound to the exchange route the message again, using the other exchanges
+/// for looking them up. Every exchange routes the message at most once, even if the bindings
+/// contain cycles, and every queue is only returned once.
+pub fn route_message(
+    exchanges: &HashMap<ExchangeName, Exchange>,
+    exchange: &Exchange,
+    routing_key: &str,
+    headers: Option<&Table>,
+) -> Option<Vec<Queue>> {
+    let mut pending = match_destinations(exchange, routing_key, headers)?;
+    let mut visited_exchanges = HashSet::from([&exchange.name]);
+    let mut visited_queues = HashSet::new();
+    let mut queues = Vec::new();
+
+    while let Some(destination) = pending.pop() {
+        match destination {
+            Destination::Queue(queue) => {
+                if visited_queues.insert(&queue.id) {
+                    queues.push(queue.clone());
+                }
+            }
+            Destination::Exchange(name) => {
+                if !visited_exchanges.insert(name) {
+                    continue;
+                }
+                // an exchange that can't route the message is not an error for the source exchange
+                if let Some(next) = exchanges
+                    .get(name)
+                    .and_then(|exchange| match_destinations(exchange, routing_key, headers))
+                {
+                    pending.extend(next);
                 }
             }
+        }
+    }
 
-            Some(value.clone())
-        })
-        .collect()
+    Some(queues)
+}
+
+/// Returns the destinations of the bindings of this exchange that match the message, without
+/// following bound exchanges
+fn match_destinations<'a>(
+    exchange: &'a Exchange,
+    routing_key: &str,
+    headers: Option<&Table>,
+) -> Option<Vec<&'a Destination>> {
+    match &exchange.kind {
+        ExchangeType::Direct { bindings } => {
+            // 3.1.3.1 - routing-key = routing-key
+            bindings
+                .get(routing_key)
+                .map(|bound| bound.iter().collect())
+        }
+        ExchangeType::Fanout { bindings } => {
+            // 3.1.3.2 - unconditionally
+            Some(bindings.iter().collect())
+        }
+        ExchangeType::Topic { bindings } => Some(bindings.match_key(routing_key)),
+        ExchangeType::Headers { bindings } => Some(
+            bindings
+                .iter()
+                .filter(|(binding, _)| match_headers(binding, headers))
+                .map(|(_, destination)| destination)
+                .collect(),
+        ),
+        ExchangeType::System => None, // unsupported
+    }
+}
+
+fn match_headers(binding: &HeadersBinding, headers: Option<&Table>) -> bool {
+    let header_matches = |(name, expected): (&String, &FieldValue)| {
+        match headers.and_then(|headers| headers.get(name)) {
+            None => false,
+            // a binding header without a value only requires the header to be present
+            Some(_) if *expected == FieldValue::Void => true,
+            Some(actual) => field_values_equal(expected, actual),
+        }
+    };
+
+    match binding.x_match {
+        HeadersMatch::All => binding.headers.iter().all(header_matches),
+        HeadersMatch::Any => binding.headers.iter().any(header_matches),
+    }
+}
+
+/// Compares two field values. Strings are equal regardless of whether they are short or long
+/// strings, because clients don't agree on which one to use.
+fn field_values_equal(a: &FieldValue, b: &FieldValue) -> bool {
+    match (string_value(a), string_value(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::routing::{match_topic, parse_topic};
+    use std::{collections::HashMap, sync::Arc};
 
-    macro_rules! match_topics_test {
-        ($name:ident {
-            patterns: $($pattern:expr),*;
-            routing_key: $routing_key:expr;
-            expected: $($expected:expr),*;
-        }) => {
-            #[test]
-            fn $name() {
-                fn inc(x: &mut u64) -> u64 { let tmp = *x; *x += 1; tmp }
+    use haesli_core::{
+        exchange::{Destination, Exchange, ExchangeName, ExchangeType},
+        methods::{FieldValue, Table},
+        queue::{Queue, QueueDeletion, QueueId, QueueInner, QueueName},
+    };
+    use haesli_datastructure::TopicTrie;
+    use parking_lot::Mutex;
+    use tokio::sync::mpsc;
 
-                let mut n = 0;
-                let n = &mut n;
+    use crate::routing::{bind, bindings, is_unused, route_message, unbind, unbind_all};
 
-                // assign each pattern a number
-                let patterns = [$((parse_topic($pattern), inc(n))),*];
+    fn queue(name: &str) -> Queue {
+        let (event_send, _) = mpsc::unbounded_channel();
+        let (publish_send, _) = mpsc::channel(1);
 
-                let matched = match_topic(&patterns, $routing_key);
-                let expected = vec![$($expected),*];
+        Arc::new(QueueInner {
+            id: QueueId::random(),
+            name: QueueName::new(name.into()),
+            messages: haesli_datastructure::MessageQueue::new(),
+            durable: false,
+            exclusive: None,
+            deletion: QueueDeletion::Manual,
+            consumers: Mutex::default(),
+            event_send,
+            publish_send,
+        })
+    }
 
-                assert_eq!(matched, expected);
-            }
+    fn direct_exchange() -> Exchange {
+        Exchange {
+            name: ExchangeName::new("direct".into()),
+            kind: ExchangeType::Direct {
+                bindings: HashMap::new(),
+            },
+            durable: false,
+            auto_delete: false,
+        }
+    }
+
+    fn bind_key(exchange: &mut Exchange, routing_key: &str, queue: Queue) {
+        bind(
+            exchange,
+            routing_key.to_owned(),
+            Table::new(),
+            Destination::Queue(queue),
+        )
+        .unwrap();
+    }
+
+    fn unbind_key(exchange: &mut Exchange, routing_key: &str, queue: &Queue) {
+        unbind(
+            exchange,
+            routing_key,
+            Table::new(),
+            &Destination::Queue(queue.clone()),
+        );
+    }
+
+    fn routed_names(exchange: &Exchange, routing_key: &str) -> Vec<String> {
+        route_headers(exchange, routing_key, None)
+    }
+
+    fn route_headers(
+        exchange: &Exchange,
+        routing_key: &str,
+        headers: Option<&Table>,
+    ) -> Vec<String> {
+        route_through(&HashMap::new(), exchange, routing_key, headers)
+    }
+
+    fn route_through(
+        exchanges: &HashMap<ExchangeName, Exchange>,
+        exchange: &Exchange,
+        routing_key: &str,
+        headers: Option<&Table>,
+    ) -> Vec<String> {
+        let mut names = route_message(exchanges, exchange, routing_key, headers)
+            .unwrap_or_default()
+            .iter()
+            .map(|queue| queue.name.to_string())
+            .collect::<Vec<_>>();
+        // the order in which queues are routed to doesn't matter
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn direct_routes_to_all_queues_with_key() {
+        let mut exchange = direct_exchange();
+        bind_key(&mut exchange, "key", queue("a"));
+        bind_key(&mut exchange, "key", queue("b"));
+        bind_key(&mut exchange, "other", queue("c"));
+
+        assert_eq!(routed_names(&exchange, "key"), ["a", "b"]);
+        assert_eq!(routed_names(&exchange, "other"), ["c"]);
+        assert!(routed_names(&exchange, "none").is_empty());
+    }
+
+    #[test]
+    fn direct_bind_is_idempotent() {
+        let mut exchange = direct_exchange();
+        let a = queue("a");
+        bind_key(&mut exchange, "key", a.clone());
+        bind_key(&mut exchange, "key", a.clone());
+
+        assert_eq!(routed_names(&exchange, "key"), ["a"]);
+
+        unbind_key(&mut exchange, "key", &a);
+
+        assert!(routed_names(&exchange, "key").is_empty());
+    }
+
+    #[test]
+    fn direct_unbind_keeps_other_bindings() {
+        let mut exchange = direct_exchange();
+        let a = queue("a");
+        bind_key(&mut exchange, "key", a.clone());
+        bind_key(&mut exchange, "other", a.clone());
+        bind_key(&mut exchange, "key", queue("b"));
+
+        unbind_key(&mut exchange, "key", &a);
+
+        assert_eq!(routed_names(&exchange, "key"), ["b"]);
+        assert_eq!(routed_names(&exchange, "other"), ["a"]);
+
+        unbind_all(&mut exchange, &Destination::Queue(a));
+
+        assert!(routed_names(&exchange, "other").is_empty());
+    }
+
+    #[test]
+    fn topic_bind_is_idempotent() {
+        let mut exchange = Exchange {
+            name: ExchangeName::new("topic".into()),
+            kind: ExchangeType::Topic {
+                bindings: TopicTrie::new(),
+            },
+            durable: false,
+            auto_delete: false,
         };
+        let a = queue("a");
+        bind_key(&mut exchange, "a.*", a.clone());
+        bind_key(&mut exchange, "a.*", a.clone());
+        bind_key(&mut exchange, "#", queue("b"));
+
+        assert_eq!(routed_names(&exchange, "a.b"), ["a", "b"]);
+
+        unbind_key(&mut exchange, "a.*", &a);
+
+        assert_eq!(routed_names(&exchange, "a.b"), ["b"]);
     }
 
-    match_topics_test!(match_spec_example_1 {
-        patterns: "*.stock.#";
-        routing_key: "usd.stock";
-        expected: 0;
-    });
-
-    match_topics_test!(match_spec_example_2 {
-        patterns: "*.stock.#";
-        routing_key: "eur.stock.db";
-        expected: 0;
-    });
-
-    match_topics_test!(match_spec_example_3 {
-        patterns: "*.stock.#";
-        routing_key: "stock.nasdaq";
-        expected: ;
-    });
-
-    match_topics_test!(match_no_wildcards {
-        patterns: "na.stock.usd", "sa.stock.peso", "stock.nasdaq", "usd.stock.na";
-        routing_key: "na.stock.usd";
-        expected: 0;
-    });
-
-    match_topics_test!(match_cursed_wildcards {
-        patterns: "*.*.*", "#.usd", "#.stock.*", "*.#", "#", "na.*";
-        routing_key: "na.stock.usd";
-        expected: 0, 1, 2, 3, 4;
-    });
-
-    match_topics_test!(match_empty_topic {
-        patterns: "", "bad";
-        routing_key: "";
-        expected: 0;
-    });
+    fn headers_exchange() -> Exchange {
+        Exchange {
+            name: ExchangeName::new("headers".into()),
+            kind: ExchangeType::Headers {
+                bindings: Vec::new(),
+            },
+            durable: false,
+            auto_delete: false,
+        }
+    }
+
+    fn table<const N: usize>(entries: [(&str, FieldValue); N]) -> Table {
+        entries
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect()
+    }
+
+    fn long_string(str: &str) -> FieldValue {
+        FieldValue::LongString(str.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn headers_match_all_and_any() {
+        let mut exchange = headers_exchange();
+        let binding = |x_match| {
+            table([
+                ("x-match", long_string(x_match)),
+                ("format", long_string("pdf")),
+                ("type", long_string("report")),
+            ])
+        };
+        for x_match in ["all", "any"] {
+            let destination = Destination::Queue(queue(x_match));
+            bind(&mut exchange, String::new(), binding(x_match), destination).unwrap();
+        }
+
+        let both = table([
+            ("format", long_string("pdf")),
+            ("type", FieldValue::ShortString("report".to_owned())),
+        ]);
+        let one = table([("format", long_string("pdf")), ("type", long_string("log"))]);
+        let none = table([("other", long_string("pdf"))]);
+
+        assert_eq!(route_headers(&exchange, "", Some(&both)), ["all", "any"]);
+        assert_eq!(route_headers(&exchange, "", Some(&one)), ["any"]);
+        assert!(route_headers(&exchange, "", Some(&none)).is_empty());
+        assert!(route_headers(&exchange, "", None).is_empty());
+    }
+
+    #[test]
+    fn headers_default_to_all_and_ignore_routing_key() {
+        let mut exchange = headers_exchange();
+        let a = Destination::Queue(queue("a"));
+        let arguments = || table([("format", long_string("pdf"))]);
+        bind(&mut exchange, "key".to_owned(), arguments(), a.clone()).unwrap();
+
+        let headers = table([("format", long_string("pdf")), ("extra", long_string("x"))]);
+        assert_eq!(route_headers(&exchange, "other", Some(&headers)), ["a"]);
+
+        unbind(&mut exchange, "key", arguments(), &a);
+        assert!(route_headers(&exchange, "other", Some(&headers)).is_empty());
+    }
+
+    #[test]
+    fn headers_invalid_x_match() {
+        let mut exchange = headers_exchange();
+        let arguments = table([("x-match", long_string("most"))]);
+
+        let destination = Destination::Queue(queue("a"));
+
+        assert!(bind(&mut exchange, String::new(), arguments, destination).is_err());
+    }
+
+    #[test]
+    fn headers_bindings_can_be_bound_again() {
+        let mut exchange = headers_exchange();
+        let arguments = table([
+            ("x-match", FieldValue::ShortString("any".to_owned())),
+            ("format", long_string("pdf")),
+        ]);
+        bind(
+            &mut exchange,
+            String::new(),
+            arguments,
+            Destination::Queue(queue("a")),
+        )
+        .unwrap();
+
+        let mut restored = headers_exchange();
+        for (routing_key, arguments, destination) in bindings(&exchange) {
+            bind(&mut restored, routing_key, arguments, destination.clone()).unwrap();
+        }
+
+        let headers = table([("format", long_string("pdf")), ("type", long_string("log"))]);
+        assert_eq!(route_headers(&restored, "", Some(&headers)), ["a"]);
+        assert_eq!(bindings(&restored).len(), 1);
+    }
+
+    fn fanout_exchange(name: &str) -> Exchange {
+        Exchange {
+            name: ExchangeName::new(name.into()),
+            kind: ExchangeType::Fanout {
+                bindings: Vec::new(),
+            },
+            durable: false,
+            auto_delete: false,
+        }
+    }
+
+    fn bind_exchange(
+        exchanges: &mut HashMap<ExchangeName, Exchange>,
+        source: &str,
+        key: &str,
+        destination: &str,
+    ) {
+        let destination = Destination::Exchange(ExchangeName::new(destination.into()));
+        let source = exchanges.get_mut(source).unwrap();
+        bind(source, key.to_owned(), Table::new(), destination).unwrap();
+    }
+
+    fn exchanges<const N: usize>(exchanges: [Exchange; N]) -> HashMap<ExchangeName, Exchange> {
+        exchanges
+            .into_iter()
+            .map(|exchange| (exchange.name.clone(), exchange))
+            .collect()
+    }
+
+    #[test]
+    fn exchange_bindings_route_to_queues_once() {
+        let a = queue("a");
+        let mut direct = direct_exchange();
+        bind_key(&mut direct, "key", a.clone());
+        let mut fanout = fanout_exchange("fanout");
+        bind_key(&mut fanout, "", a);
+        bind_key(&mut fanout, "", queue("b"));
+
+        let mut exchanges = exchanges([direct, fanout]);
+        bind_exchange(&mut exchanges, "direct", "key", "fanout");
+
+        let direct = &exchanges["direct"];
+        assert_eq!(route_through(&exchanges, direct, "key", None), ["a", "b"]);
+        assert!(route_through(&exchanges, direct, "other", None).is_empty());
+    }
+
+    #[test]
+    fn exchange_binding_cycles_are_followed_once() {
+        let mut end = fanout_exchange("end");
+        bind_key(&mut end, "", queue("a"));
+
+        let mut exchanges = exchanges([fanout_exchange("x"), fanout_exchange("y"), end]);
+        bind_exchange(&mut exchanges, "x", "", "x");
+        bind_exchange(&mut exchanges, "x", "", "y");
+        bind_exchange(&mut exchanges, "y", "", "x");
+        bind_exchange(&mut exchanges, "y", "", "end");
+        bind_exchange(&mut exchanges, "end", "", "x");
+
+        for name in ["x", "y", "end"] {
+            assert_eq!(route_through(&exchanges, &exchanges[name], "", None), ["a"]);
+        }
+    }
+
+    #[test]
+    fn unbind_exchange_keeps_queue_with_same_name() {
+        let mut exchanges = exchanges([fanout_exchange("x"), fanout_exchange("a")]);
+        bind_key(exchanges.get_mut("x").unwrap(), "", queue("a"));
+        bind_exchange(&mut exchanges, "x", "", "a");
+
+        let destination = Destination::Exchange(ExchangeName::new("a".into()));
+        unbind_all(exchanges.get_mut("x").unwrap(), &destination);
+
+        assert_eq!(route_through(&exchanges, &exchanges["x"], "", None), ["a"]);
+        assert!(!is_unused(&exchanges["x"]));
+    }
 }
@@ -0,0 +1,110 @@
+#![warn(rust_2018_idioms)]
+
+//! Storage on the local disk, used for the entities that survive a restart of the broker.
+//!
+//! This crate only knows how to store values safely, what is stored is decided by the broker.
+
+mod log;
+mod snapshot;
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+pub use log::{LogOptions, MessageLog, Record, RecordId, SyncPolicy};
+pub use snapshot::Snapshot;
+
+const TOPOLOGY_FILE: &str = "topology.json";
+const QUEUES_DIR: &str = "queues";
+
+/// The data directory of the broker.
+///
+/// It contains a [`Snapshot`] of the durable topology, and a [`MessageLog`] for every durable
+/// queue, which are identified by the name of their directory.
+#[derive(Debug)]
+pub struct Store {
+    dir: PathBuf,
+    topology: Snapshot,
+    log_options: LogOptions,
+}
+
+impl Store {
+    pub fn open(dir: impl Into<PathBuf>, log_options: LogOptions) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(dir.join(QUEUES_DIR))?;
+
+        Ok(Self {
+            topology: Snapshot::new(dir.join(TOPOLOGY_FILE)),
+            dir,
+            log_options,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn topology(&self) -> &Snapshot {
+        &self.topology
+    }
+
+    /// Creates a new, empty message log with the name, which is used to open it again
+    pub fn create_message_log(&self, name: &str) -> io::Result<MessageLog> {
+        let (log, records) = self.open_message_log(name)?;
+
+        if !records.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("message log {name} already exists"),
+            ));
+        }
+
+        Ok(log)
+    }
+
+    /// Opens an existing message log, returning the records that are in it
+    pub fn open_message_log(&self, name: &str) -> io::Result<(MessageLog, Vec<Record>)> {
+        MessageLog::open(self.dir.join(QUEUES_DIR).join(name), self.log_options)
+    }
+
+    /// Returns the names of all message logs
+    pub fn message_logs(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+
+        for entry in fs::read_dir(self.dir.join(QUEUES_DIR))? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// Deletes a message log that is not opened
+    pub fn delete_message_log(&self, name: &str) -> io::Result<()> {
+        fs::remove_dir_all(self.dir.join(QUEUES_DIR).join(name))
+    }
+}
+
+/// Makes sure that the creation, renaming and removal of files in the directory are on the disk
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_: &Path) -> io::Result<()> {
+    // directories can't be opened as files everywhere
+    Ok(())
+}
@@ -0,0 +1,774 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+use crate::sync_dir;
+
+/// Identifies a record in a [`MessageLog`]. Record IDs increase in the order records are appended.
+pub type RecordId = u64;
+
+const SEGMENT_EXTENSION: &str = "log";
+
+/// `[payload length: u32][crc32: u32][kind: u8][id: u64]`, all little endian. The checksum covers
+/// everything after it, including the payload.
+const RECORD_HEADER_SIZE: usize = 4 + 4 + 1 + 8;
+
+const KIND_APPEND: u8 = 1;
+const KIND_REMOVE: u8 = 2;
+
+/// When the log waits until appended records are on the disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Every appended record is synced right away
+    Always,
+    /// Appended records are synced at most once per interval. Records that are appended in the
+    /// meantime can be lost when the machine crashes.
+    Interval(Duration),
+    /// Syncing is left to the operating system
+    Never,
+}
+
+impl FromStr for SyncPolicy {
+    type Err = String;
+
+    /// Parses `always`, `never` or an interval in milliseconds
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            millis => millis
+                .parse()
+                .map(|millis| Self::Interval(Duration::from_millis(millis)))
+                .map_err(|_| format!("expected `always`, `never` or milliseconds, got `{s}`")),
+        }
+    }
+}
+
+impl Display for SyncPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Always => f.write_str("always"),
+            Self::Interval(interval) => write!(f, "{}", interval.as_millis()),
+            Self::Never => f.write_str("never"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LogOptions {
+    /// A new segment is started once the current one is larger than this
+    pub segment_size: u64,
+    pub sync: SyncPolicy,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            segment_size: 16 * 1024 * 1024,
+            sync: SyncPolicy::Always,
+        }
+    }
+}
+
+/// A record that was still in the log when it was opened
+#[derive(Debug, PartialEq, Eq)]
+pub struct Record {
+    pub id: RecordId,
+    pub payload: Vec<u8>,
+}
+
+/// An append-only log of records, for example the persistent messages of a queue.
+///
+/// The log is split into segment files. Records are appended to the newest segment, and removing
+/// a record appends a marker that the record is gone. Segments that only contain removed records
+/// are deleted, and once most of the log consists of removed records, the records that are still
+/// in the oldest segments are appended again, so that these segments can be deleted as well.
+///
+/// A record that was only partially written, for example because the broker crashed while writing
+/// it, is detected using its checksum and cut off when the log is opened.
+#[derive(Debug)]
+pub struct MessageLog {
+    dir: PathBuf,
+    options: LogOptions,
+    /// All segments, including the active one, by their number
+    segments: BTreeMap<u64, Segment>,
+    active: ActiveSegment,
+    next_id: RecordId,
+    /// Where the records that are still in the log are stored
+    records: HashMap<RecordId, Location>,
+    last_sync: Instant,
+    /// Whether appended records have been written since the last sync
+    unsynced: bool,
+}
+
+#[derive(Debug, Default)]
+struct Segment {
+    size: u64,
+    /// The size of the records in the segment that are still in the log
+    live_size: u64,
+}
+
+#[derive(Debug)]
+struct ActiveSegment {
+    number: u64,
+    file: File,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    segment: u64,
+    /// The offset of the payload in the segment file
+    offset: u64,
+    len: u32,
+}
+
+impl Location {
+    fn record_size(&self) -> u64 {
+        RECORD_HEADER_SIZE as u64 + u64::from(self.len)
+    }
+}
+
+impl MessageLog {
+    /// Opens the log in the directory, creating it if it doesn't exist yet. Returns the records
+    /// that are still in the log, ordered by their ID.
+    pub fn open(dir: impl Into<PathBuf>, options: LogOptions) -> io::Result<(Self, Vec<Record>)> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut segments = BTreeMap::new();
+        let mut records = BTreeMap::<RecordId, (Location, Vec<u8>)>::new();
+        let mut next_id = 1;
+
+        for number in segment_numbers(&dir)? {
+            let path = segment_path(&dir, number);
+            let data = fs::read(&path)?;
+
+            let valid_len = read_segment(&data, |kind, id, offset, payload| {
+                next_id = next_id.max(id + 1);
+                match kind {
+                    KIND_APPEND => {
+                        let location = Location {
+                            segment: number,
+                            offset,
+                            len: payload.len() as u32,
+                        };
+                        records.insert(id, (location, payload.to_vec()));
+                    }
+                    _ => {
+                        records.remove(&id);
+                    }
+                }
+            });
+
+            if valid_len < data.len() {
+                warn!(
+                    path = %path.display(),
+                    valid_len,
+                    len = data.len(),
+                    "Cutting off incomplete records at the end of the log segment"
+                );
+                OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(valid_len as u64)?;
+            }
+
+            segments.insert(
+                number,
+                Segment {
+                    size: valid_len as u64,
+                    live_size: 0,
+                },
+            );
+        }
+
+        for (location, _) in records.values() {
+            if let Some(segment) = segments.get_mut(&location.segment) {
+                segment.live_size += location.record_size();
+            }
+        }
+
+        let active_number = match segments.iter().next_back() {
+            Some((number, segment)) if segment.size < options.segment_size => *number,
+            Some((number, _)) => number + 1,
+            None => 1,
+        };
+        segments.entry(active_number).or_default();
+
+        let active = ActiveSegment {
+            number: active_number,
+            file: open_segment(&dir, active_number)?,
+        };
+        sync_dir(&dir)?;
+
+        let mut log = Self {
+            dir,
+            options,
+            segments,
+            active,
+            next_id,
+            records: HashMap::new(),
+            last_sync: Instant::now(),
+            unsynced: false,
+        };
+
+        let records = records
+            .into_iter()
+            .map(|(id, (location, payload))| {
+                log.records.insert(id, location);
+                Record { id, payload }
+            })
+            .collect();
+
+        log.compact()?;
+
+        Ok((log, records))
+    }
+
+    /// The directory the log is stored in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The amount of records in the log
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The ID of the next appended record
+    pub fn next_id(&self) -> RecordId {
+        self.next_id
+    }
+
+    /// Appends a record with an ID that is at least [`next_id`], so that the IDs keep increasing.
+    /// Callers can hand out IDs before the records are appended this way.
+    ///
+    /// The record is not synced, [`sync_deadline`] tells when that has to happen.
+    ///
+    /// [`next_id`]: MessageLog::next_id
+    /// [`sync_deadline`]: MessageLog::sync_deadline
+    pub fn append(&mut self, id: RecordId, payload: &[u8]) -> io::Result<()> {
+        if id < self.next_id {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("record ID {id} is lower than the next ID {}", self.next_id),
+            ));
+        }
+        self.next_id = id + 1;
+
+        self.write_record(KIND_APPEND, id, payload)?;
+        self.unsynced = true;
+
+        self.roll_segment()
+    }
+
+    /// When the appended records have to be synced according to the sync policy. `None` if there
+    /// is nothing to sync or syncing is left to the operating system.
+    pub fn sync_deadline(&self) -> Option<Instant> {
+        if !self.unsynced {
+            return None;
+        }
+
+        match self.options.sync {
+            SyncPolicy::Always => Some(self.last_sync),
+            SyncPolicy::Interval(interval) => Some(self.last_sync + interval),
+            SyncPolicy::Never => None,
+        }
+    }
+
+    /// Removes a record from the log. Removing a record that is not in the log does nothing.
+    ///
+    /// The removal is not synced on its own. If it gets lost in a crash, the record is still in
+    /// the log after the restart.
+    pub fn remove(&mut self, id: RecordId) -> io::Result<()> {
+        let Some(location) = self.records.remove(&id) else {
+            return Ok(());
+        };
+
+        if let Some(segment) = self.segments.get_mut(&location.segment) {
+            segment.live_size -= location.record_size();
+        }
+
+        self.write_record(KIND_REMOVE, id, &[])?;
+        self.roll_segment()?;
+        self.compact()
+    }
+
+    /// Waits until everything that was written to the log is on the disk
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.active.file.sync_data()?;
+        self.last_sync = Instant::now();
+        self.unsynced = false;
+        Ok(())
+    }
+
+    /// Deletes the log with all of its records
+    pub fn delete(self) -> io::Result<()> {
+        fs::remove_dir_all(&self.dir)
+    }
+
+    fn write_record(&mut self, kind: u8, id: RecordId, payload: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record is too large"))?;
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + payload.len());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&[0; 4]); // the checksum is filled in below
+        record.push(kind);
+        record.extend_from_slice(&id.to_le_bytes());
+        record.extend_from_slice(payload);
+
+        let crc = crc32fast::hash(&record[8..]);
+        record[4..8].copy_from_slice(&crc.to_le_bytes());
+
+        self.active.file.write_all(&record)?;
+
+        let segment = self.segments.entry(self.active.number).or_default();
+        let offset = segment.size + RECORD_HEADER_SIZE as u64;
+        segment.size += record.len() as u64;
+
+        if kind == KIND_APPEND {
+            segment.live_size += record.len() as u64;
+            self.records.insert(
+                id,
+                Location {
+                    segment: self.active.number,
+                    offset,
+                    len,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Starts a new segment if the active one is full
+    fn roll_segment(&mut self) -> io::Result<()> {
+        if self.segments[&self.active.number].size < self.options.segment_size {
+            return Ok(());
+        }
+
+        // the old segment is done, so it has to be complete on the disk before moving on
+        if self.unsynced {
+            self.sync()?;
+        }
+
+        let number = self.active.number + 1;
+        self.active = ActiveSegment {
+            number,
+            file: open_segment(&self.dir, number)?,
+        };
+        self.segments.insert(number, Segment::default());
+
+        sync_dir(&self.dir)
+    }
+
+    /// Deletes the oldest segments while more than half of the log consists of removed records.
+    /// The records of a deleted segment that are still in the log are appended again.
+    ///
+    /// Only the oldest segment can be deleted, because a segment can contain the removal of
+    /// records that are stored in older segments.
+    fn compact(&mut self) -> io::Result<()> {
+        loop {
+            let Some((&number, segment)) = self.segments.iter().next() else {
+                return Ok(());
+            };
+
+            if number == self.active.number {
+                return Ok(());
+            }
+
+            let (size, live_size) = self
+                .segments
+                .values()
+                .fold((0, 0), |(size, live_size), segment| {
+                    (size + segment.size, live_size + segment.live_size)
+                });
+
+            if segment.live_size > 0 {
+                if live_size * 2 >= size {
+                    return Ok(());
+                }
+                self.copy_live_records(number)?;
+            }
+
+            fs::remove_file(segment_path(&self.dir, number))?;
+            self.segments.remove(&number);
+            sync_dir(&self.dir)?;
+        }
+    }
+
+    /// Appends the records of the segment that are still in the log again, so that the segment
+    /// can be deleted
+    fn copy_live_records(&mut self, number: u64) -> io::Result<()> {
+        let mut live = self
+            .records
+            .iter()
+            .filter(|(_, location)| location.segment == number)
+            .map(|(id, location)| (*id, *location))
+            .collect::<Vec<_>>();
+        live.sort_by_key(|(_, location)| location.offset);
+
+        let mut file = File::open(segment_path(&self.dir, number))?;
+
+        for (id, location) in live {
+            let mut payload = vec![0; location.len as usize];
+            file.seek(SeekFrom::Start(location.offset))?;
+            file.read_exact(&mut payload)?;
+
+            self.write_record(KIND_APPEND, id, &payload)?;
+            // the copies have to be synced when their segment is done, just like appended records
+            self.unsynced = true;
+            self.roll_segment()?;
+        }
+
+        // the copies have to be on the disk before the segment with the originals is deleted
+        self.sync()
+    }
+}
+
+/// Calls the function with the kind, ID, payload offset and payload of every valid record in the
+/// segment. Returns the length of the valid part of the segment.
+fn read_segment(data: &[u8], mut f: impl FnMut(u8, RecordId, u64, &[u8])) -> usize {
+    let mut pos = 0;
+
+    while let Some(header) = data.get(pos..pos + RECORD_HEADER_SIZE) {
+        let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let kind = header[8];
+        let id = u64::from_le_bytes(header[9..17].try_into().unwrap());
+
+        let payload_start = pos + RECORD_HEADER_SIZE;
+        let Some(payload) = data.get(payload_start..payload_start + len) else {
+            break;
+        };
+
+        if crc32fast::hash(&data[pos + 8..payload_start + len]) != crc
+            || !matches!(kind, KIND_APPEND | KIND_REMOVE)
+        {
+            break;
+        }
+
+        f(kind, id, payload_start as u64, payload);
+
+        pos = payload_start + len;
+    }
+
+    pos
+}
+
+fn segment_path(dir: &Path, number: u64) -> PathBuf {
+    dir.join(format!("{number:020}.{SEGMENT_EXTENSION}"))
+}
+
+fn open_segment(dir: &Path, number: u64) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(segment_path(dir, number))
+}
+
+/// Returns the numbers of the segments in the directory, in ascending order
+fn segment_numbers(dir: &Path) -> io::Result<Vec<u64>> {
+    let mut numbers = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+        if let Some(number) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok())
+        {
+            numbers.push(number);
+        }
+    }
+
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, OpenOptions},
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    use super::{
+        segment_numbers, segment_path, LogOptions, MessageLog, Record, RecordId, SyncPolicy,
+    };
+
+    fn options(segment_size: u64) -> LogOptions {
+        LogOptions {
+            segment_size,
+            sync: SyncPolicy::Always,
+        }
+    }
+
+    fn append(log: &mut MessageLog, payload: &[u8]) -> RecordId {
+        let id = log.next_id();
+        log.append(id, payload).unwrap();
+        id
+    }
+
+    fn payloads(records: &[Record]) -> Vec<&[u8]> {
+        records
+            .iter()
+            .map(|record| record.payload.as_slice())
+            .collect()
+    }
+
+    fn last_segment_len(dir: &Path) -> u64 {
+        let last = *segment_numbers(dir).unwrap().last().unwrap();
+        fs::metadata(segment_path(dir, last)).unwrap().len()
+    }
+
+    fn truncate_last_segment(dir: &Path, by: u64) {
+        let last = *segment_numbers(dir).unwrap().last().unwrap();
+        let file = OpenOptions::new()
+            .write(true)
+            .open(segment_path(dir, last))
+            .unwrap();
+        let len = file.metadata().unwrap().len();
+        file.set_len(len - by).unwrap();
+    }
+
+    #[test]
+    fn reopen_returns_records_that_were_not_removed() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let (mut log, records) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        assert!(records.is_empty());
+
+        let a = append(&mut log, b"a");
+        let b = append(&mut log, b"b");
+        append(&mut log, b"c");
+        log.remove(b).unwrap();
+        assert!(a < b);
+        drop(log);
+
+        let (mut log, records) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        assert_eq!(payloads(&records), [b"a", b"c"]);
+
+        // IDs are not reused after reopening
+        assert!(append(&mut log, b"d") > records[1].id);
+    }
+
+    #[test]
+    fn append_requires_increasing_ids() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let (mut log, _) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        log.append(5, b"a").unwrap();
+        assert!(log.append(5, b"b").is_err());
+        assert_eq!(log.next_id(), 6);
+
+        assert!(log.sync_deadline().is_some());
+        log.sync().unwrap();
+        assert_eq!(log.sync_deadline(), None);
+    }
+
+    #[test]
+    fn sync_deadline_follows_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let interval = Duration::from_secs(60);
+
+        for (policy, expected) in [
+            (SyncPolicy::Always, Some(Duration::ZERO)),
+            (SyncPolicy::Interval(interval), Some(interval)),
+            (SyncPolicy::Never, None),
+        ] {
+            let options = LogOptions {
+                segment_size: 1024,
+                sync: policy,
+            };
+            let (mut log, _) =
+                MessageLog::open(dir.path().join(policy.to_string()), options).unwrap();
+            assert_eq!(log.sync_deadline(), None);
+
+            log.sync().unwrap();
+            let synced_at = Instant::now();
+            append(&mut log, b"a");
+
+            // the deadline is measured from the last sync
+            let deadline = log.sync_deadline();
+            assert_eq!(deadline.is_some(), expected.is_some(), "{policy}");
+            if let (Some(deadline), Some(expected)) = (deadline, expected) {
+                assert!(deadline <= synced_at + expected, "{policy}");
+                assert!(
+                    deadline + Duration::from_secs(1) > synced_at + expected,
+                    "{policy}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn record_cut_off_in_payload_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let (mut log, _) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        append(&mut log, b"complete");
+        let len_before = last_segment_len(dir.path());
+        append(&mut log, b"cut off in the middle");
+        drop(log);
+
+        truncate_last_segment(dir.path(), 5);
+
+        let (mut log, records) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        assert_eq!(payloads(&records), [b"complete"]);
+        // the broken record is removed, so new records are not appended after it
+        assert_eq!(last_segment_len(dir.path()), len_before);
+
+        append(&mut log, b"new");
+        drop(log);
+
+        let (_, records) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        assert_eq!(payloads(&records), [b"complete".as_slice(), b"new"]);
+    }
+
+    #[test]
+    fn record_cut_off_in_header_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let (mut log, _) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        append(&mut log, b"complete");
+        append(&mut log, b"");
+        drop(log);
+
+        // the empty record only consists of its header
+        truncate_last_segment(dir.path(), 3);
+
+        let (_, records) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        assert_eq!(payloads(&records), [b"complete"]);
+    }
+
+    #[test]
+    fn corrupted_record_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let (mut log, _) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        append(&mut log, b"complete");
+        append(&mut log, b"corrupted");
+        drop(log);
+
+        let last = *segment_numbers(dir.path()).unwrap().last().unwrap();
+        let path = segment_path(dir.path(), last);
+        let mut data = fs::read(&path).unwrap();
+        *data.last_mut().unwrap() ^= 0xff;
+        fs::write(&path, data).unwrap();
+
+        let (_, records) = MessageLog::open(dir.path(), options(1024)).unwrap();
+        assert_eq!(payloads(&records), [b"complete"]);
+    }
+
+    #[test]
+    fn removed_records_are_compacted() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let (mut log, _) = MessageLog::open(dir.path(), options(64)).unwrap();
+        let ids = (0..20)
+            .map(|i| append(&mut log, format!("message {i}").as_bytes()))
+            .collect::<Vec<_>>();
+        assert!(segment_numbers(dir.path()).unwrap().len() > 5);
+
+        for id in &ids[..19] {
+            log.remove(*id).unwrap();
+        }
+
+        // only the segment with the remaining record and the active segment are left
+        assert!(segment_numbers(dir.path()).unwrap().len() <= 2);
+        assert_eq!(log.len(), 1);
+        drop(log);
+
+        let (_, records) = MessageLog::open(dir.path(), options(64)).unwrap();
+        assert_eq!(payloads(&records), [b"message 19"]);
+    }
+
+    #[test]
+    fn old_records_are_copied_forward() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let (mut log, _) = MessageLog::open(dir.path(), options(64)).unwrap();
+        let first = append(&mut log, b"never removed");
+        let ids = (0..20)
+            .map(|i| append(&mut log, format!("message {i}").as_bytes()))
+            .collect::<Vec<_>>();
+
+        for id in ids {
+            log.remove(id).unwrap();
+        }
+
+        // the first segment is gone, even though one of its records is still in the log
+        assert!(segment_numbers(dir.path()).unwrap().len() <= 2);
+        drop(log);
+
+        let (_, records) = MessageLog::open(dir.path(), options(64)).unwrap();
+        assert_eq!(
+            records,
+            [Record {
+                id: first,
+                payload: b"never removed".to_vec(),
+            }]
+        );
+    }
+
+    #[test]
+    fn copied_records_start_new_segments() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let (mut log, _) = MessageLog::open(dir.path(), options(64)).unwrap();
+        let live = (0..10)
+            .map(|i| append(&mut log, format!("live {i}").as_bytes()))
+            .collect::<Vec<_>>();
+        let removed = (0..40)
+            .map(|i| append(&mut log, format!("removed {i}").as_bytes()))
+            .collect::<Vec<_>>();
+
+        for id in removed {
+            log.remove(id).unwrap();
+        }
+
+        // a segment only grows past its size by the record that filled it
+        for number in segment_numbers(dir.path()).unwrap() {
+            let len = fs::metadata(segment_path(dir.path(), number))
+                .unwrap()
+                .len();
+            assert!(len < 64 + 32, "segment {number} has {len} bytes");
+        }
+        drop(log);
+
+        let (_, records) = MessageLog::open(dir.path(), options(64)).unwrap();
+        assert_eq!(
+            records.iter().map(|record| record.id).collect::<Vec<_>>(),
+            live
+        );
+    }
+
+    #[test]
+    fn parse_sync_policy() {
+        assert_eq!("always".parse(), Ok(SyncPolicy::Always));
+        assert_eq!("never".parse(), Ok(SyncPolicy::Never));
+        assert_eq!(
+            "200".parse(),
+            Ok(SyncPolicy::Interval(std::time::Duration::from_millis(200)))
+        );
+        assert!("sometimes".parse::<SyncPolicy>().is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::sync_dir;
+
+/// A file that contains a single value, which is replaced as a whole every time it is saved.
+///
+/// The new value is written to a temporary file first, which then atomically replaces the old
+/// file. A crash while saving leaves either the old or the new value behind, never a mix of both.
+#[derive(Debug)]
+pub struct Snapshot {
+    path: PathBuf,
+}
+
+impl Snapshot {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the value, or returns `None` if nothing has been saved yet
+    pub fn load<T: DeserializeOwned>(&self) -> io::Result<Option<T>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let value = serde_json::from_reader(io::BufReader::new(file))?;
+
+        Ok(Some(value))
+    }
+
+    /// Saves the value and waits until it is on the disk
+    pub fn save<T: Serialize>(&self, value: &T) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer(&mut writer, value)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+
+        fs::rename(&tmp_path, &self.path)?;
+
+        // the rename itself is only durable once the directory is synced as well
+        if let Some(dir) = self.path.parent() {
+            sync_dir(dir)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::Snapshot;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Value {
+        names: Vec<String>,
+    }
+
+    #[test]
+    fn load_without_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = Snapshot::new(dir.path().join("value.json"));
+
+        assert_eq!(snapshot.load::<Value>().unwrap(), None);
+    }
+
+    #[test]
+    fn save_replaces_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = Snapshot::new(dir.path().join("value.json"));
+
+        let first = Value {
+            names: vec!["a".to_owned(), "b".to_owned()],
+        };
+        let second = Value {
+            names: vec!["c".to_owned()],
+        };
+
+        snapshot.save(&first).unwrap();
+        snapshot.save(&second).unwrap();
+
+        assert_eq!(snapshot.load::<Value>().unwrap(), Some(second));
+        // the temporary file is gone after saving
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
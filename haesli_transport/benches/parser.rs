@@ -17,7 +17,7 @@ fn random_method_from_seed(seed: u128) -> Method {
 
 fn serialize_method(method: Method) -> Vec<u8> {
     let mut writer = Vec::new();
-    methods::write::write_method(method, &mut writer).unwrap();
+    methods::write::write_method(&method, &mut writer).unwrap();
     writer
 }
 
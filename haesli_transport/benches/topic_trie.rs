@@ -0,0 +1,98 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use haesli_datastructure::TopicTrie;
+
+/// Whether the words of a routing key match the segments of a topic pattern, by going back to the
+/// last `#` and letting it match one more word when the rest of the key doesn't match.
+fn topic_matches(pattern: &[&str], key: &[&str]) -> bool {
+    let mut pat_idx = 0;
+    let mut key_idx = 0;
+    let mut backtrack = None;
+
+    while key_idx < key.len() {
+        match pattern.get(pat_idx) {
+            Some(&"*") => {
+                pat_idx += 1;
+                key_idx += 1;
+            }
+            Some(&"#") => {
+                backtrack = Some((pat_idx, key_idx));
+                pat_idx += 1;
+            }
+            Some(word) if *word == key[key_idx] => {
+                pat_idx += 1;
+                key_idx += 1;
+            }
+            _ => match backtrack {
+                Some((multi_idx, multi_key_idx)) => {
+                    backtrack = Some((multi_idx, multi_key_idx + 1));
+                    pat_idx = multi_idx + 1;
+                    key_idx = multi_key_idx + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[pat_idx..].iter().all(|segment| *segment == "#")
+}
+
+/// How topic bindings were routed before the trie: every pattern is matched against the key.
+fn linear_match<'a, T>(bindings: &'a [(Vec<&str>, T)], routing_key: &str) -> Vec<&'a T> {
+    let key = routing_key.split('.').collect::<Vec<_>>();
+
+    bindings
+        .iter()
+        .filter(|(pattern, _)| topic_matches(pattern, &key))
+        .map(|(_, value)| value)
+        .collect()
+}
+
+/// Per-user bindings, like they are used for notifications, plus a few global wildcard bindings
+fn patterns(users: usize) -> Vec<String> {
+    (0..users)
+        .flat_map(|user| {
+            [
+                format!("user.{user}.notification.*"),
+                format!("user.{user}.message.#"),
+            ]
+        })
+        .chain([
+            "user.*.notification.urgent".to_owned(),
+            "#.audit".to_owned(),
+        ])
+        .collect()
+}
+
+fn route_topic(c: &mut Criterion) {
+    let mut group = c.benchmark_group("route topic");
+
+    for users in [100, 1_000, 10_000] {
+        let patterns = patterns(users);
+
+        let mut trie = TopicTrie::new();
+        for (idx, pattern) in patterns.iter().enumerate() {
+            trie.entry(pattern).push(idx);
+        }
+
+        let linear = patterns
+            .iter()
+            .enumerate()
+            .map(|(idx, pattern)| (pattern.split('.').collect(), idx))
+            .collect::<Vec<_>>();
+
+        let key = format!("user.{}.notification.urgent", users / 2);
+
+        group.bench_with_input(BenchmarkId::new("trie", users), &key, |b, key| {
+            b.iter(|| black_box(trie.match_key(black_box(key))))
+        });
+
+        group.bench_with_input(BenchmarkId::new("linear", users), &key, |b, key| {
+            b.iter(|| black_box(linear_match(&linear, black_box(key))))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, route_topic);
+criterion_main!(benches);
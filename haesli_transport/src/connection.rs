@@ -3,17 +3,19 @@ use std::{
 };
 
 use anyhow::{anyhow, Context};
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use haesli_core::{
+    confirm::{ConfirmEvent, ConfirmReceiver},
     connection::{
         Channel, ChannelInner, ChannelNum, ConEventReceiver, ConEventSender, Connection,
         ConnectionEvent, ConnectionId, ContentHeader,
     },
     message::{MessageId, MessageInner, RoutingInformation},
     methods::{
-        BasicPublish, ChannelClose, ChannelCloseOk, ChannelOpenOk, ConnectionClose,
-        ConnectionCloseOk, ConnectionOpen, ConnectionOpenOk, ConnectionStart, ConnectionStartOk,
-        ConnectionTune, ConnectionTuneOk, FieldValue, Longstr, Method, ReplyCode, ReplyText, Table,
+        BasicAck, BasicNack, BasicPublish, ChannelClose, ChannelCloseOk, ChannelOpenOk,
+        ConnectionClose, ConnectionCloseOk, ConnectionOpen, ConnectionOpenOk, ConnectionStart,
+        ConnectionStartOk, ConnectionTune, ConnectionTuneOk, FieldValue, Longstr, Method,
+        ReplyCode, ReplyText, Table,
     },
     GlobalData, SingleVec,
 };
@@ -55,6 +57,8 @@ pub struct TransportChannel {
 pub struct TransportConnection {
     id: ConnectionId,
     stream: TcpStream,
+    /// Bytes read from the stream that don't make up a complete frame yet
+    read_buf: BytesMut,
     max_frame_size: MaxFrameSize,
     heartbeat_delay: u16,
     channel_max: u16,
@@ -67,6 +71,8 @@ pub struct TransportConnection {
     event_sender: ConEventSender,
     /// To receive events from other futures
     event_receiver: ConEventReceiver,
+    /// To receive the publisher confirms of all channels
+    confirm_receiver: ConfirmReceiver,
 
     handlers: Handlers,
 }
@@ -91,22 +97,24 @@ impl TransportConnection {
         stream: TcpStream,
         global_con: Connection,
         global_data: GlobalData,
-        method_queue_send: ConEventSender,
         method_queue_recv: ConEventReceiver,
+        confirm_receiver: ConfirmReceiver,
         handlers: Handlers,
     ) -> Self {
         Self {
             id,
             stream,
+            read_buf: BytesMut::new(),
             max_frame_size: FRAME_SIZE_MIN_MAX,
             heartbeat_delay: HEARTBEAT_DELAY,
             channel_max: CHANNEL_MAX,
             next_timeout: Box::pin(time::sleep(DEFAULT_TIMEOUT)),
+            event_sender: global_con.event_sender.clone(),
             global_con,
             channels: HashMap::with_capacity(4),
             global_data,
-            event_sender: method_queue_send,
             event_receiver: method_queue_recv,
+            confirm_receiver,
             handlers,
         }
     }
@@ -223,9 +231,10 @@ impl TransportConnection {
     }
 
     async fn recv_method(&mut self) -> Result<Method> {
-        let start_ok_frame = frame::read_frame(&mut self.stream, self.max_frame_size)
-            .await
-            .context("read from stream, peer disconnected")?;
+        let start_ok_frame =
+            frame::read_frame(&mut self.stream, &mut self.read_buf, self.max_frame_size)
+                .await
+                .context("read from stream, peer disconnected")?;
 
         ensure_conn(start_ok_frame.kind == FrameType::Method)?;
 
@@ -320,28 +329,63 @@ impl TransportConnection {
     async fn main_loop(&mut self) -> Result<()> {
         loop {
             select! {
-                frame = frame::read_frame(&mut self.stream, self.max_frame_size) => {
+                // reading a frame is cancellation safe, so no data is lost if an event arrives first
+                frame = frame::read_frame(&mut self.stream, &mut self.read_buf, self.max_frame_size) => {
                     let frame = frame.context("read from stream, peer disconnected")?;
                     self.handle_frame(frame).await?;
                 }
                 queued_method = self.event_receiver.recv() => {
-                    match queued_method {
-                        Some(ConnectionEvent::Method(channel, method)) => {
-                            trace!(?channel, ?method, "Received method from event queue");
-                            self.send_method(channel, &method).await?
-                        }
-                        Some(ConnectionEvent::MethodContent(channel, method, header, body)) => {
-                            trace!(?channel, ?method, ?header, ?body, "Received method with body from event queue");
-                            self.send_method_content(channel, &method, header, &body).await?
-                        }
-                        Some(ConnectionEvent::Shutdown) => return self.close(0, "".to_owned()).await,
-                        None => {}
+                    if let Some(event) = queued_method {
+                        trace!(?event, "Received event from event queue");
+                        self.handle_event(event).await?;
+                    }
+                }
+                confirm = self.confirm_receiver.recv() => {
+                    if let Some(confirm) = confirm {
+                        self.send_confirm(confirm).await?;
                     }
                 }
             }
         }
     }
 
+    async fn send_confirm(&mut self, confirm: ConfirmEvent) -> Result<()> {
+        let ConfirmEvent {
+            channel,
+            delivery_tag,
+            ack,
+        } = confirm;
+
+        let method = if ack {
+            Method::BasicAck(BasicAck {
+                delivery_tag,
+                multiple: false,
+            })
+        } else {
+            Method::BasicNack(BasicNack {
+                delivery_tag,
+                multiple: false,
+                requeue: false,
+            })
+        };
+
+        self.send_method(channel, &method).await
+    }
+
+    async fn handle_event(&mut self, event: ConnectionEvent) -> Result<()> {
+        match event {
+            ConnectionEvent::Method(channel, method) => self.send_method(channel, &method).await,
+            ConnectionEvent::MethodContent(channel, method, header, body) => {
+                self.send_method_content(channel, &method, header, &body)
+                    .await
+            }
+            ConnectionEvent::Shutdown => {
+                self.close(0, "".to_owned()).await?;
+                Err(ProtocolError::GracefullyClosed.into())
+            }
+        }
+    }
+
     #[tracing::instrument(skip(self), level = "debug")]
     async fn handle_frame(&mut self, frame: Frame) -> Result<()> {
         let channel = frame.channel;
@@ -353,7 +397,7 @@ impl TransportConnection {
                 Ok(()) /* Nothing here, just the `reset_timeout` above */
             }
             FrameType::Header => self.dispatch_header(frame),
-            FrameType::Body => self.dispatch_body(frame),
+            FrameType::Body => self.dispatch_body(frame).await,
         };
 
         match result {
@@ -418,11 +462,11 @@ impl TransportConnection {
                     .clone();
 
                 // call into haesli_messaging to handle the method
-                // it returns the response method that we are supposed to send
-                let return_method = (self.handlers.handle_method)(channel_handle, method)?;
+                // it returns the responses that we are supposed to send
+                let responses = (self.handlers.handle_method)(channel_handle, method).await?;
 
-                if let Some(method) = return_method {
-                    self.send_method(frame.channel, &method).await?;
+                for response in responses {
+                    self.handle_event(response).await?;
                 }
             }
         }
@@ -452,7 +496,7 @@ impl TransportConnection {
             })
     }
 
-    fn dispatch_body(&mut self, frame: Frame) -> Result<()> {
+    async fn dispatch_body(&mut self, frame: Frame) -> Result<()> {
         let channel = self
             .channels
             .get_mut(&frame.channel)
@@ -477,6 +521,7 @@ impl TransportConnection {
                 {
                     Ordering::Equal => {
                         self.process_method_with_body(*method, header, vec, frame.channel)
+                            .await
                     }
                     Ordering::Greater => Err(ConException::Todo.into()),
                     Ordering::Less => Ok(()), // wait for next body
@@ -485,7 +530,7 @@ impl TransportConnection {
         }
     }
 
-    fn process_method_with_body(
+    async fn process_method_with_body(
         &mut self,
         method: Method,
         header: ContentHeader,
@@ -518,8 +563,13 @@ impl TransportConnection {
 
             let channel = self.channels.get(&channel).ok_or(ConException::Todo)?;
 
-            (self.handlers.handle_basic_publish)(channel.global_chan.clone(), message)?;
-            //haesli_messaging::methods::publish(channel.global_chan.clone(), message)?;
+            // a message that can't be routed might have to be returned to the publisher
+            let response =
+                (self.handlers.handle_basic_publish)(channel.global_chan.clone(), message).await?;
+
+            if let Some(response) = response {
+                self.handle_event(response).await?;
+            }
             Ok(())
         } else {
             Err(ConException::Todo.into())
@@ -657,16 +707,12 @@ impl TransportConnection {
 
         match self.recv_method().await {
             Ok(Method::ConnectionCloseOk(_)) => Ok(()),
-            Ok(method) => {
-                return Err(TransError::Other(anyhow!(
-                    "Received wrong method after closing, method: {method:?}"
-                )));
-            }
-            Err(err) => {
-                return Err(TransError::Other(anyhow!(
-                    "Failed to receive Connection.CloseOk method after closing, err: {err}"
-                )));
-            }
+            Ok(method) => Err(TransError::Other(anyhow!(
+                "Received wrong method after closing, method: {method:?}"
+            ))),
+            Err(err) => Err(TransError::Other(anyhow!(
+                "Failed to receive Connection.CloseOk method after closing, err: {err}"
+            ))),
         }
     }
 }
@@ -674,6 +720,7 @@ impl TransportConnection {
 impl Drop for TransportConnection {
     fn drop(&mut self) {
         self.global_con.close();
+        (self.handlers.handle_connection_close)(self.global_con.clone());
     }
 }
 
@@ -688,8 +735,25 @@ fn server_properties(host: SocketAddr) -> Table {
         FieldValue::LongString(str.into())
     }
 
+    let capabilities = HashMap::from([
+        ("basic.nack".to_owned(), FieldValue::Boolean(true)),
+        (
+            "consumer_cancel_notify".to_owned(),
+            FieldValue::Boolean(true),
+        ),
+        (
+            "exchange_exchange_bindings".to_owned(),
+            FieldValue::Boolean(true),
+        ),
+        ("publisher_confirms".to_owned(), FieldValue::Boolean(true)),
+    ]);
+
     let host_str = host.ip().to_string();
     HashMap::from([
+        (
+            "capabilities".to_owned(),
+            FieldValue::FieldTable(capabilities),
+        ),
         ("host".to_owned(), ls(host_str)),
         ("product".to_owned(), ls("haesli")),
         ("version".to_owned(), ls("0.1.0")),
@@ -1,10 +1,11 @@
 use std::{
     fmt::{Debug, Formatter},
+    io,
     num::NonZeroUsize,
 };
 
 use anyhow::Context;
-use bytes::Bytes;
+use bytes::{Buf, Bytes, BytesMut};
 use haesli_core::connection::{ChannelNum, ContentHeader};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::trace;
@@ -279,39 +280,72 @@ where
     Ok(())
 }
 
-pub async fn read_frame<R>(r: &mut R, max_frame_size: MaxFrameSize) -> Result<Frame>
+/// The type, channel and size at the start of every frame
+const FRAME_HEADER_SIZE: usize = 7;
+
+/// Reads the next frame.
+///
+/// Bytes that have been read but don't make up a complete frame yet are kept in `buf`, so
+/// dropping the future before it completes doesn't lose any data. The next call continues with
+/// the same frame.
+pub async fn read_frame<R>(
+    r: &mut R,
+    buf: &mut BytesMut,
+    max_frame_size: MaxFrameSize,
+) -> Result<Frame>
 where
     R: AsyncReadExt + Unpin + Send,
 {
-    let kind = r.read_u8().await?;
-    let channel = r.read_u16().await?;
-    let channel = ChannelNum::new(channel);
-    let size = r.read_u32().await?;
-
-    let mut payload = vec![0; size.try_into().unwrap()];
-    r.read_exact(&mut payload).await?;
+    loop {
+        if let Some(frame) = parse_frame(buf, max_frame_size)? {
+            trace!(?frame, "Received frame");
+            return Ok(frame);
+        }
 
-    let frame_end = r.read_u8().await?;
+        if r.read_buf(buf).await? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+    }
+}
 
-    if frame_end != REQUIRED_FRAME_END {
-        return Err(ProtocolError::Fatal.into());
+/// Takes the first frame out of the buffer, or returns `None` if it isn't complete yet.
+fn parse_frame(buf: &mut BytesMut, max_frame_size: MaxFrameSize) -> Result<Option<Frame>> {
+    if buf.len() < FRAME_HEADER_SIZE {
+        return Ok(None);
     }
 
-    if payload.len() > max_frame_size.as_usize() {
+    let kind = buf[0];
+    let channel = ChannelNum::new(u16::from_be_bytes([buf[1], buf[2]]));
+    let size = u32::from_be_bytes([buf[3], buf[4], buf[5], buf[6]]);
+    let size = usize::try_from(size).unwrap();
+
+    // checked before the payload is read, so that it isn't buffered needlessly
+    if size > max_frame_size.as_usize() {
         return Err(ConException::FrameError.into());
     }
 
+    let frame_size = FRAME_HEADER_SIZE + size + 1;
+    if buf.len() < frame_size {
+        buf.reserve(frame_size - buf.len());
+        return Ok(None);
+    }
+
+    let mut payload = buf.split_to(frame_size);
+    payload.advance(FRAME_HEADER_SIZE);
+
+    let frame_end = payload[size];
+    if frame_end != REQUIRED_FRAME_END {
+        return Err(ProtocolError::Fatal.into());
+    }
+    payload.truncate(size);
+
     let kind = parse_frame_type(kind, channel)?;
 
-    let frame = Frame {
+    Ok(Some(Frame {
         kind,
         channel,
-        payload: payload.into(),
-    };
-
-    trace!(?frame, "Received frame");
-
-    Ok(frame)
+        payload: payload.freeze(),
+    }))
 }
 
 fn parse_frame_type(kind: u8, channel: ChannelNum) -> Result<FrameType> {
@@ -332,7 +366,7 @@ fn parse_frame_type(kind: u8, channel: ChannelNum) -> Result<FrameType> {
 
 #[cfg(test)]
 mod tests {
-    use bytes::Bytes;
+    use bytes::{Bytes, BytesMut};
 
     use crate::frame::{ChannelNum, Frame, FrameType, MaxFrameSize};
 
@@ -357,7 +391,7 @@ mod tests {
             super::REQUIRED_FRAME_END,
         ];
 
-        let frame = super::read_frame(&mut bytes, MaxFrameSize::new(10000))
+        let frame = super::read_frame(&mut bytes, &mut BytesMut::new(), MaxFrameSize::new(10000))
             .await
             .unwrap();
         assert_eq!(
@@ -369,4 +403,43 @@ mod tests {
             }
         );
     }
+
+    #[tokio::test]
+    async fn read_frame_continues_after_partial_read() {
+        let bytes: &[u8] = &[
+            /*type*/ 1,
+            /*channel*/ 0,
+            1,
+            /*size*/ 0,
+            0,
+            0,
+            2,
+            /*payload*/ 4,
+            5,
+            /*frame-end*/ super::REQUIRED_FRAME_END,
+        ];
+        let mut buf = BytesMut::new();
+
+        // the reader ends in the middle of the frame, like a read that was cancelled
+        let mut start = &bytes[..5];
+        assert!(
+            super::read_frame(&mut start, &mut buf, MaxFrameSize::new(10000))
+                .await
+                .is_err()
+        );
+
+        let mut rest = &bytes[5..];
+        let frame = super::read_frame(&mut rest, &mut buf, MaxFrameSize::new(10000))
+            .await
+            .unwrap();
+        assert_eq!(
+            frame,
+            Frame {
+                kind: FrameType::Method,
+                channel: ChannelNum::new(1),
+                payload: Bytes::from_static(&[4, 5]),
+            }
+        );
+        assert!(buf.is_empty());
+    }
 }
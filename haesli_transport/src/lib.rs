@@ -3,7 +3,7 @@
 mod connection;
 mod error;
 mod frame;
-mod methods;
+pub mod methods;
 mod sasl;
 #[cfg(test)]
 mod tests;
@@ -14,12 +14,11 @@ use std::{future::Future, net::SocketAddr};
 
 use anyhow::Context;
 use haesli_core::{
-    connection::{Channel, ConnectionEvent},
-    error::ProtocolError,
+    connection::{Channel, Connection, ConnectionEvent},
     message::Message,
     methods::Method,
     queue::QueueEvent,
-    GlobalData,
+    GlobalData, HandlerFuture, SingleVec,
 };
 use tokio::{net, net::TcpStream, select};
 use tracing::{info, info_span, Instrument};
@@ -28,8 +27,10 @@ use crate::connection::TransportConnection;
 
 #[derive(Clone, Copy)]
 pub struct Handlers {
-    pub handle_method: fn(Channel, Method) -> Result<Option<Method>, ProtocolError>,
-    pub handle_basic_publish: fn(Channel, Message) -> Result<(), ProtocolError>,
+    pub handle_method: fn(Channel, Method) -> HandlerFuture<SingleVec<ConnectionEvent>>,
+    pub handle_basic_publish: fn(Channel, Message) -> HandlerFuture<Option<ConnectionEvent>>,
+    /// Called once the connection has been closed and its channels and consumers are removed
+    pub handle_connection_close: fn(Connection),
 }
 
 pub async fn connection_loop(
@@ -66,12 +67,14 @@ fn handle_con(global_data: GlobalData, connection: (TcpStream, SocketAddr), hand
     let span = info_span!("client-connection", %id);
 
     let (method_send, method_recv) = tokio::sync::mpsc::channel(10);
+    let (confirm_send, confirm_recv) = tokio::sync::mpsc::unbounded_channel();
 
     let connection_handle = haesli_core::connection::ConnectionInner::new(
         id,
         peer_addr,
         global_data.clone(),
-        method_send.clone(),
+        method_send,
+        confirm_send,
     );
 
     let mut global_data_guard = global_data.lock();
@@ -84,8 +87,8 @@ fn handle_con(global_data: GlobalData, connection: (TcpStream, SocketAddr), hand
         stream,
         connection_handle,
         global_data.clone(),
-        method_send,
         method_recv,
+        confirm_recv,
         handlers,
     );
 
@@ -106,7 +109,7 @@ async fn handle_shutdown(global_data: GlobalData) -> anyhow::Result<()> {
     for queue in lock.queues.values() {
         queue
             .event_send
-            .try_send(QueueEvent::Shutdown)
+            .send(QueueEvent::Shutdown)
             .context("failed to stop queue worker")?;
     }
 
@@ -12,7 +12,7 @@ pub mod parse {
     pub type IResult<'a, T> = nom::IResult<&'a [u8], T, TransError>;
 
     pub fn parse_method(input: &[u8]) -> Result<(&[u8], Method), nom::Err<TransError>> {
-        alt((connection, channel, exchange, queue, basic, tx))(input)
+        alt((connection, channel, exchange, queue, basic, confirm, tx))(input)
     }
     fn domain_class_id(input: &[u8]) -> IResult<'_, ClassId> {
         short(input)
@@ -345,6 +345,10 @@ pub mod parse {
             exchange_declare_ok,
             exchange_delete,
             exchange_delete_ok,
+            exchange_bind,
+            exchange_bind_ok,
+            exchange_unbind,
+            exchange_unbind_ok,
         ))(input)
         .map_err(fail_err("class exchange"))
     }
@@ -362,7 +366,7 @@ pub mod parse {
         let (input, bits) = bit(input, 5).map_err(fail_err("field passive in method declare"))?;
         let passive = bits[0];
         let durable = bits[1];
-        let reserved_2 = bits[2];
+        let auto_delete = bits[2];
         let reserved_3 = bits[3];
         let no_wait = bits[4];
         let (input, arguments) =
@@ -375,7 +379,7 @@ pub mod parse {
                 r#type,
                 passive,
                 durable,
-                reserved_2,
+                auto_delete,
                 reserved_3,
                 no_wait,
                 arguments,
@@ -412,6 +416,66 @@ pub mod parse {
         let (input, _) = tag(21_u16.to_be_bytes())(input)?;
         Ok((input, Method::ExchangeDeleteOk(ExchangeDeleteOk {})))
     }
+    fn exchange_bind(input: &[u8]) -> IResult<'_, Method> {
+        let (input, _) = tag(30_u16.to_be_bytes())(input)?;
+        let (input, reserved_1) =
+            domain_short(input).map_err(fail_err("field reserved-1 in method bind"))?;
+        let (input, destination) =
+            domain_exchange_name(input).map_err(fail_err("field destination in method bind"))?;
+        let (input, source) =
+            domain_exchange_name(input).map_err(fail_err("field source in method bind"))?;
+        let (input, routing_key) =
+            domain_shortstr(input).map_err(fail_err("field routing-key in method bind"))?;
+        let (input, bits) = bit(input, 1).map_err(fail_err("field no-wait in method bind"))?;
+        let no_wait = bits[0];
+        let (input, arguments) =
+            domain_table(input).map_err(fail_err("field arguments in method bind"))?;
+        Ok((
+            input,
+            Method::ExchangeBind(ExchangeBind {
+                reserved_1,
+                destination,
+                source,
+                routing_key,
+                no_wait,
+                arguments,
+            }),
+        ))
+    }
+    fn exchange_bind_ok(input: &[u8]) -> IResult<'_, Method> {
+        let (input, _) = tag(31_u16.to_be_bytes())(input)?;
+        Ok((input, Method::ExchangeBindOk(ExchangeBindOk {})))
+    }
+    fn exchange_unbind(input: &[u8]) -> IResult<'_, Method> {
+        let (input, _) = tag(40_u16.to_be_bytes())(input)?;
+        let (input, reserved_1) =
+            domain_short(input).map_err(fail_err("field reserved-1 in method unbind"))?;
+        let (input, destination) =
+            domain_exchange_name(input).map_err(fail_err("field destination in method unbind"))?;
+        let (input, source) =
+            domain_exchange_name(input).map_err(fail_err("field source in method unbind"))?;
+        let (input, routing_key) =
+            domain_shortstr(input).map_err(fail_err("field routing-key in method unbind"))?;
+        let (input, bits) = bit(input, 1).map_err(fail_err("field no-wait in method unbind"))?;
+        let no_wait = bits[0];
+        let (input, arguments) =
+            domain_table(input).map_err(fail_err("field arguments in method unbind"))?;
+        Ok((
+            input,
+            Method::ExchangeUnbind(ExchangeUnbind {
+                reserved_1,
+                destination,
+                source,
+                routing_key,
+                no_wait,
+                arguments,
+            }),
+        ))
+    }
+    fn exchange_unbind_ok(input: &[u8]) -> IResult<'_, Method> {
+        let (input, _) = tag(51_u16.to_be_bytes())(input)?;
+        Ok((input, Method::ExchangeUnbindOk(ExchangeUnbindOk {})))
+    }
     fn queue(input: &[u8]) -> IResult<'_, Method> {
         let (input, _) = tag(50_u16.to_be_bytes())(input)?;
         alt((
@@ -606,6 +670,7 @@ pub mod parse {
             basic_recover_async,
             basic_recover,
             basic_recover_ok,
+            basic_nack,
         ))(input)
         .map_err(fail_err("class basic"))
     }
@@ -849,6 +914,36 @@ pub mod parse {
         let (input, _) = tag(111_u16.to_be_bytes())(input)?;
         Ok((input, Method::BasicRecoverOk(BasicRecoverOk {})))
     }
+    fn basic_nack(input: &[u8]) -> IResult<'_, Method> {
+        let (input, _) = tag(120_u16.to_be_bytes())(input)?;
+        let (input, delivery_tag) =
+            domain_delivery_tag(input).map_err(fail_err("field delivery-tag in method nack"))?;
+        let (input, bits) = bit(input, 2).map_err(fail_err("field multiple in method nack"))?;
+        let multiple = bits[0];
+        let requeue = bits[1];
+        Ok((
+            input,
+            Method::BasicNack(BasicNack {
+                delivery_tag,
+                multiple,
+                requeue,
+            }),
+        ))
+    }
+    fn confirm(input: &[u8]) -> IResult<'_, Method> {
+        let (input, _) = tag(85_u16.to_be_bytes())(input)?;
+        alt((confirm_select, confirm_select_ok))(input).map_err(fail_err("class confirm"))
+    }
+    fn confirm_select(input: &[u8]) -> IResult<'_, Method> {
+        let (input, _) = tag(10_u16.to_be_bytes())(input)?;
+        let (input, bits) = bit(input, 1).map_err(fail_err("field no-wait in method select"))?;
+        let no_wait = bits[0];
+        Ok((input, Method::ConfirmSelect(ConfirmSelect { no_wait })))
+    }
+    fn confirm_select_ok(input: &[u8]) -> IResult<'_, Method> {
+        let (input, _) = tag(11_u16.to_be_bytes())(input)?;
+        Ok((input, Method::ConfirmSelectOk(ConfirmSelectOk {})))
+    }
     fn tx(input: &[u8]) -> IResult<'_, Method> {
         let (input, _) = tag(90_u16.to_be_bytes())(input)?;
         alt((
@@ -1015,7 +1110,7 @@ pub mod write {
                 r#type,
                 passive,
                 durable,
-                reserved_2,
+                auto_delete,
                 reserved_3,
                 no_wait,
                 arguments,
@@ -1025,7 +1120,7 @@ pub mod write {
                 shortstr(exchange, &mut writer)?;
                 shortstr(r#type, &mut writer)?;
                 bit(
-                    &[*passive, *durable, *reserved_2, *reserved_3, *no_wait],
+                    &[*passive, *durable, *auto_delete, *reserved_3, *no_wait],
                     &mut writer,
                 )?;
                 table(arguments, &mut writer)?;
@@ -1047,6 +1142,44 @@ pub mod write {
             Method::ExchangeDeleteOk(ExchangeDeleteOk {}) => {
                 writer.write_all(&[0, 40, 0, 21])?;
             }
+            Method::ExchangeBind(ExchangeBind {
+                reserved_1,
+                destination,
+                source,
+                routing_key,
+                no_wait,
+                arguments,
+            }) => {
+                writer.write_all(&[0, 40, 0, 30])?;
+                short(reserved_1, &mut writer)?;
+                shortstr(destination, &mut writer)?;
+                shortstr(source, &mut writer)?;
+                shortstr(routing_key, &mut writer)?;
+                bit(&[*no_wait], &mut writer)?;
+                table(arguments, &mut writer)?;
+            }
+            Method::ExchangeBindOk(ExchangeBindOk {}) => {
+                writer.write_all(&[0, 40, 0, 31])?;
+            }
+            Method::ExchangeUnbind(ExchangeUnbind {
+                reserved_1,
+                destination,
+                source,
+                routing_key,
+                no_wait,
+                arguments,
+            }) => {
+                writer.write_all(&[0, 40, 0, 40])?;
+                short(reserved_1, &mut writer)?;
+                shortstr(destination, &mut writer)?;
+                shortstr(source, &mut writer)?;
+                shortstr(routing_key, &mut writer)?;
+                bit(&[*no_wait], &mut writer)?;
+                table(arguments, &mut writer)?;
+            }
+            Method::ExchangeUnbindOk(ExchangeUnbindOk {}) => {
+                writer.write_all(&[0, 40, 0, 51])?;
+            }
             Method::QueueDeclare(QueueDeclare {
                 reserved_1,
                 queue,
@@ -1282,6 +1415,22 @@ pub mod write {
             Method::BasicRecoverOk(BasicRecoverOk {}) => {
                 writer.write_all(&[0, 60, 0, 111])?;
             }
+            Method::BasicNack(BasicNack {
+                delivery_tag,
+                multiple,
+                requeue,
+            }) => {
+                writer.write_all(&[0, 60, 0, 120])?;
+                longlong(delivery_tag, &mut writer)?;
+                bit(&[*multiple, *requeue], &mut writer)?;
+            }
+            Method::ConfirmSelect(ConfirmSelect { no_wait }) => {
+                writer.write_all(&[0, 85, 0, 10])?;
+                bit(&[*no_wait], &mut writer)?;
+            }
+            Method::ConfirmSelectOk(ConfirmSelectOk {}) => {
+                writer.write_all(&[0, 85, 0, 11])?;
+            }
             Method::TxSelect(TxSelect {}) => {
                 writer.write_all(&[0, 90, 0, 10])?;
             }
@@ -1314,7 +1463,7 @@ mod random {
     impl<R: Rng> RandomMethod<R> for Method {
         #[allow(unused_variables)]
         fn random(rng: &mut R) -> Self {
-            match rng.gen_range(0u32..6) {
+            match rng.gen_range(0u32..7) {
                 0 => match rng.gen_range(0u32..10) {
                     0 => Method::ConnectionStart(ConnectionStart {
                         version_major: RandomMethod::random(rng),
@@ -1384,14 +1533,14 @@ mod random {
                     5 => Method::ChannelCloseOk(ChannelCloseOk {}),
                     _ => unreachable!(),
                 },
-                2 => match rng.gen_range(0u32..4) {
+                2 => match rng.gen_range(0u32..8) {
                     0 => Method::ExchangeDeclare(ExchangeDeclare {
                         reserved_1: RandomMethod::random(rng),
                         exchange: RandomMethod::random(rng),
                         r#type: RandomMethod::random(rng),
                         passive: RandomMethod::random(rng),
                         durable: RandomMethod::random(rng),
-                        reserved_2: RandomMethod::random(rng),
+                        auto_delete: RandomMethod::random(rng),
                         reserved_3: RandomMethod::random(rng),
                         no_wait: RandomMethod::random(rng),
                         arguments: RandomMethod::random(rng),
@@ -1404,6 +1553,24 @@ mod random {
                         no_wait: RandomMethod::random(rng),
                     }),
                     3 => Method::ExchangeDeleteOk(ExchangeDeleteOk {}),
+                    4 => Method::ExchangeBind(ExchangeBind {
+                        reserved_1: RandomMethod::random(rng),
+                        destination: RandomMethod::random(rng),
+                        source: RandomMethod::random(rng),
+                        routing_key: RandomMethod::random(rng),
+                        no_wait: RandomMethod::random(rng),
+                        arguments: RandomMethod::random(rng),
+                    }),
+                    5 => Method::ExchangeBindOk(ExchangeBindOk {}),
+                    6 => Method::ExchangeUnbind(ExchangeUnbind {
+                        reserved_1: RandomMethod::random(rng),
+                        destination: RandomMethod::random(rng),
+                        source: RandomMethod::random(rng),
+                        routing_key: RandomMethod::random(rng),
+                        no_wait: RandomMethod::random(rng),
+                        arguments: RandomMethod::random(rng),
+                    }),
+                    7 => Method::ExchangeUnbindOk(ExchangeUnbindOk {}),
                     _ => unreachable!(),
                 },
                 3 => match rng.gen_range(0u32..10) {
@@ -1459,7 +1626,7 @@ mod random {
                     }),
                     _ => unreachable!(),
                 },
-                4 => match rng.gen_range(0u32..17) {
+                4 => match rng.gen_range(0u32..18) {
                     0 => Method::BasicQos(BasicQos {
                         prefetch_size: RandomMethod::random(rng),
                         prefetch_count: RandomMethod::random(rng),
@@ -1536,9 +1703,21 @@ mod random {
                         requeue: RandomMethod::random(rng),
                     }),
                     16 => Method::BasicRecoverOk(BasicRecoverOk {}),
+                    17 => Method::BasicNack(BasicNack {
+                        delivery_tag: RandomMethod::random(rng),
+                        multiple: RandomMethod::random(rng),
+                        requeue: RandomMethod::random(rng),
+                    }),
+                    _ => unreachable!(),
+                },
+                5 => match rng.gen_range(0u32..2) {
+                    0 => Method::ConfirmSelect(ConfirmSelect {
+                        no_wait: RandomMethod::random(rng),
+                    }),
+                    1 => Method::ConfirmSelectOk(ConfirmSelectOk {}),
                     _ => unreachable!(),
                 },
-                5 => match rng.gen_range(0u32..6) {
+                6 => match rng.gen_range(0u32..6) {
                     0 => Method::TxSelect(TxSelect {}),
                     1 => Method::TxSelectOk(TxSelectOk {}),
                     2 => Method::TxCommit(TxCommit {}),
@@ -82,7 +82,7 @@ pub fn longlong(input: &[u8]) -> IResult<'_, Longlong> {
 }
 
 pub fn bit(input: &[u8], amount: usize) -> IResult<'_, Vec<Bit>> {
-    let octets = (amount + 7) / 8;
+    let octets = amount.div_ceil(8);
     let (input, bytes) = take(octets)(input)?;
 
     let mut vec = Vec::new();
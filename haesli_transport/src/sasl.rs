@@ -7,8 +7,10 @@ use haesli_core::error::ConException;
 use crate::error::Result;
 
 pub struct PlainUser {
+    #[allow(dead_code)]
     pub authorization_identity: String,
     pub authentication_identity: String,
+    #[allow(dead_code)]
     pub password: String,
 }
 
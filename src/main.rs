@@ -1,9 +1,10 @@
 #![warn(rust_2018_idioms)]
 
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
+use haesli_store::{LogOptions, Store, SyncPolicy};
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
 
@@ -17,6 +18,16 @@ struct Args {
     /// Displays logs in a flat structure, otherwise as a tree
     #[clap(long)]
     flat_log: bool,
+
+    /// The directory where durable exchanges, queues, bindings and persistent messages are stored.
+    /// If it is not set, they are only kept in memory.
+    #[clap(long)]
+    data_dir: Option<PathBuf>,
+
+    /// When persistent messages are synced to the disk: `always` before they are confirmed,
+    /// at most every given amount of milliseconds, or `never` to leave it to the operating system
+    #[clap(long, default_value = "always")]
+    fsync: SyncPolicy,
 }
 
 #[tokio::main]
@@ -27,6 +38,17 @@ async fn main() -> Result<()> {
 
     let global_data = haesli_core::GlobalData::default();
 
+    if let Some(data_dir) = &args.data_dir {
+        let log_options = LogOptions {
+            sync: args.fsync,
+            ..LogOptions::default()
+        };
+
+        Store::open(data_dir, log_options)
+            .and_then(|store| haesli_messaging::persistence::restore(&global_data, store))
+            .with_context(|| format!("failed to restore data from {}", data_dir.display()))?;
+    }
+
     if args.dashboard {
         let global_data = global_data.clone();
         tokio::spawn(async move { haesli_dashboard::start_dashboard(global_data).await });
@@ -34,7 +56,8 @@ async fn main() -> Result<()> {
 
     let handlers = haesli_transport::Handlers {
         handle_method: haesli_messaging::methods::handle_method,
-        handle_basic_publish: haesli_messaging::methods::publish,
+        handle_basic_publish: haesli_messaging::methods::handle_basic_publish,
+        handle_connection_close: haesli_messaging::methods::connection_closed,
     };
 
     let res = haesli_transport::connection_loop(global_data, terminate(), handlers).await;
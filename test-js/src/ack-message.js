@@ -0,0 +1,42 @@
+/*
+This test consumes a message and acknowledges it.
+Acknowledging it a second time is expected to close the channel with a precondition-failed error.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'ack-queue-8347';
+
+await channel.assertQueue(QUEUE);
+
+const received = new Promise((resolve) => {
+  channel.consume(QUEUE, (msg) => {
+    console.log(`Received message with delivery tag ${msg.fields.deliveryTag}`);
+    resolve(msg);
+  });
+});
+
+await channel.sendToQueue(QUEUE, Buffer.from('STOP'));
+
+const msg = await received;
+assert(msg.fields.deliveryTag > 0, 'delivery tag must not be zero');
+
+channel.ack(msg);
+
+const closed = new Promise((resolve) => {
+  channel.on('error', (err) => {
+    console.log(`Channel closed: ${err.message}`);
+    resolve(err);
+  });
+});
+
+// acknowledging the same message twice is an error
+channel.ack(msg);
+
+const err = await closed;
+assert(err.code === 406, `expected precondition-failed, got ${err.code}`);
+
+await connection.close();
@@ -0,0 +1,40 @@
+/*
+This test declares an auto-delete queue and an auto-delete exchange it is bound to, and
+cancels the only consumer of the queue.
+It expects the queue to be deleted, and the exchange to be deleted with its last binding.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const QUEUE = 'auto-delete-queue-2290';
+const EXCHANGE = 'auto-delete-exchange-2290';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+await channel.assertExchange(EXCHANGE, 'fanout', { autoDelete: true });
+await channel.assertQueue(QUEUE, { autoDelete: true });
+await channel.bindQueue(QUEUE, EXCHANGE, '');
+
+const { consumerTag } = await channel.consume(QUEUE, () => {});
+await channel.cancel(consumerTag);
+
+// the queue deletes itself asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+let failed = false;
+await channel.checkQueue(QUEUE).catch(() => {
+  failed = true;
+});
+assert(failed, 'auto-delete queue was not deleted');
+
+// the channel was closed by the failed declare
+const newChannel = await connection.createChannel();
+
+failed = false;
+await newChannel.checkExchange(EXCHANGE).catch(() => {
+  failed = true;
+});
+assert(failed, 'auto-delete exchange was not deleted');
+
+await connection.close();
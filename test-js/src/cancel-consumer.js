@@ -0,0 +1,31 @@
+/*
+This test starts a consumer, cancels it and sends a message afterwards.
+It expects the cancelled consumer not to receive the message, so that it can be fetched with Basic.Get.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'cancel-queue-2291';
+
+await channel.assertQueue(QUEUE);
+
+const { consumerTag } = await channel.consume(QUEUE, () => {
+  throw new Error('cancelled consumer received a message');
+});
+
+await channel.cancel(consumerTag);
+console.log(`Cancelled consumer "${consumerTag}"`);
+
+channel.sendToQueue(QUEUE, Buffer.from('STOP'));
+
+// the message is put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const msg = await channel.get(QUEUE, { noAck: true });
+assert(msg && msg.content.toString() === 'STOP', 'message was not in the queue');
+
+await channel.close();
+await connection.close();
@@ -0,0 +1,41 @@
+/*
+This test sends messages to a queue before consuming from it.
+It expects the queued messages to be delivered in order once the consumer is registered.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'consume-backlog-queue-4419';
+const MESSAGES = ['first', 'second', 'third'];
+
+await channel.assertQueue(QUEUE);
+
+for (const message of MESSAGES) {
+  channel.sendToQueue(QUEUE, Buffer.from(message));
+}
+
+// the messages are put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const received = [];
+
+await new Promise((resolve) => {
+  channel.consume(QUEUE, (msg) => {
+    received.push(msg.content.toString());
+    channel.ack(msg);
+    if (received.length === MESSAGES.length) {
+      resolve();
+    }
+  });
+});
+
+assert(
+  received.join() === MESSAGES.join(),
+  `messages arrived in the wrong order: ${received.join()}`
+);
+
+await channel.close();
+await connection.close();
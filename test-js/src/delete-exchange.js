@@ -0,0 +1,29 @@
+/*
+This test declares an exchange, binds a queue to it and deletes it.
+It expects deletion with `ifUnused` to fail while the queue is bound, and normal deletion to succeed.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'delete-exchange-queue-1138';
+const EXCHANGE = 'delete-exchange-6624';
+
+await channel.assertQueue(QUEUE);
+await channel.assertExchange(EXCHANGE, 'fanout');
+await channel.bindQueue(QUEUE, EXCHANGE, '');
+
+let failed = false;
+await channel.deleteExchange(EXCHANGE, { ifUnused: true }).catch(() => {
+  failed = true;
+});
+assert(failed, 'deleted an exchange that is in use');
+
+// the channel was closed by the failed deletion
+const newChannel = await connection.createChannel();
+await newChannel.deleteExchange(EXCHANGE);
+
+await newChannel.close();
+await connection.close();
@@ -0,0 +1,29 @@
+/*
+This test declares a queue with a message in it and deletes it.
+It expects the deleted message count to be reported, and the queue to be gone afterwards.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'delete-queue-5571';
+
+await channel.assertQueue(QUEUE);
+
+channel.sendToQueue(QUEUE, Buffer.from('STOP'));
+
+// the message is put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const { messageCount } = await channel.deleteQueue(QUEUE);
+assert(messageCount === 1, `wrong deleted message count: ${messageCount}`);
+
+const { messageCount: newCount } = await channel.assertQueue(QUEUE);
+assert(newCount === 0, 'queue was not deleted');
+
+await channel.deleteQueue(QUEUE);
+
+await channel.close();
+await connection.close();
@@ -0,0 +1,50 @@
+/*
+This test binds a fanout exchange to a topic exchange, and a queue to both of them.
+It expects a message sent to the topic exchange to arrive in the queue exactly once,
+even though the bindings form a cycle.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'exchange-chain-queue-4821';
+const SOURCE = 'exchange-chain-source-1937';
+const DESTINATION = 'exchange-chain-destination-7362';
+
+await channel.assertQueue(QUEUE);
+await channel.assertExchange(SOURCE, 'topic');
+await channel.assertExchange(DESTINATION, 'fanout');
+
+await channel.bindExchange(DESTINATION, SOURCE, 'stock.#');
+await channel.bindExchange(SOURCE, DESTINATION, '');
+await channel.bindQueue(QUEUE, SOURCE, 'stock.*');
+await channel.bindQueue(QUEUE, DESTINATION, '');
+
+channel.publish(SOURCE, 'stock.usd', Buffer.from('message'));
+
+// the message is put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const msg = await channel.get(QUEUE, { noAck: true });
+assert(msg && msg.content.toString() === 'message', 'did not receive the message');
+
+const empty = await channel.get(QUEUE);
+assert(empty === false, 'received the message more than once');
+
+await channel.unbindExchange(DESTINATION, SOURCE, 'stock.#');
+await channel.unbindQueue(QUEUE, SOURCE, 'stock.*');
+
+channel.publish(SOURCE, 'stock.usd', Buffer.from('unrouted'));
+
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const unbound = await channel.get(QUEUE);
+assert(unbound === false, 'received a message after unbinding the exchange');
+
+await channel.deleteExchange(DESTINATION);
+await channel.deleteExchange(SOURCE);
+
+await channel.close();
+await connection.close();
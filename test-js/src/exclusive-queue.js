@@ -0,0 +1,49 @@
+/*
+This test declares an exclusive queue and tries to use it from a second connection.
+It expects the second connection to be locked out, and the queue to be deleted
+once the declaring connection closes.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const QUEUE = 'exclusive-queue-6613';
+
+const owner = await connectAmqp();
+const ownerChannel = await owner.createChannel();
+
+await ownerChannel.assertQueue(QUEUE, { exclusive: true });
+
+const other = await connectAmqp();
+
+let channel = await other.createChannel();
+let code;
+await channel.checkQueue(QUEUE).catch((err) => {
+  code = err.code;
+});
+assert(code === 405, `wrong error code for passive declare: ${code}`);
+
+// the channel was closed by the failed declare
+channel = await other.createChannel();
+code = undefined;
+await channel.consume(QUEUE, () => {}).catch((err) => {
+  code = err.code;
+});
+assert(code === 405, `wrong error code for consume: ${code}`);
+
+// the owner can still use the queue
+await ownerChannel.bindQueue(QUEUE, 'amqp.direct', 'exclusive');
+await ownerChannel.purgeQueue(QUEUE);
+
+await owner.close();
+
+// the queue is deleted after the connection has been closed
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+channel = await other.createChannel();
+let failed = false;
+await channel.checkQueue(QUEUE).catch(() => {
+  failed = true;
+});
+assert(failed, 'exclusive queue was not deleted with its connection');
+
+await other.close();
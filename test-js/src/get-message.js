@@ -0,0 +1,36 @@
+/*
+This test polls a queue using Basic.Get.
+It expects to receive the messages in order, and `false` once the queue is empty.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'get-queue-8127';
+
+await channel.assertQueue(QUEUE);
+
+channel.sendToQueue(QUEUE, Buffer.from('first'));
+channel.sendToQueue(QUEUE, Buffer.from('second'));
+
+// the messages are put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const first = await channel.get(QUEUE);
+assert(first.content.toString() === 'first', 'received the wrong message');
+assert(
+  first.fields.messageCount === 1,
+  `wrong message count: ${first.fields.messageCount}`
+);
+channel.ack(first);
+
+const second = await channel.get(QUEUE, { noAck: true });
+assert(second.content.toString() === 'second', 'received the wrong message');
+
+const empty = await channel.get(QUEUE);
+assert(empty === false, 'received a message from an empty queue');
+
+await channel.close();
+await connection.close();
@@ -0,0 +1,40 @@
+/*
+This test binds a queue to a headers exchange with `x-match: all` and sends two messages to it.
+It expects only the message with all the headers of the binding to arrive.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'headers-queue-6158';
+const EXCHANGE = 'headers-exchange-2719';
+
+await channel.assertQueue(QUEUE);
+await channel.assertExchange(EXCHANGE, 'headers');
+
+await channel.bindQueue(QUEUE, EXCHANGE, '', {
+  'x-match': 'all',
+  format: 'pdf',
+  type: 'report',
+});
+
+channel.publish(EXCHANGE, '', Buffer.from('partial'), {
+  headers: { format: 'pdf' },
+});
+channel.publish(EXCHANGE, '', Buffer.from('complete'), {
+  headers: { format: 'pdf', type: 'report' },
+});
+
+// the messages are put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const msg = await channel.get(QUEUE, { noAck: true });
+assert(msg && msg.content.toString() === 'complete', 'did not receive the message');
+
+const empty = await channel.get(QUEUE);
+assert(empty === false, 'received a message that does not match all headers');
+
+await channel.close();
+await connection.close();
@@ -0,0 +1,42 @@
+/*
+This test publishes two messages that can't be routed to any queue, one of them mandatory.
+It expects only the mandatory message to be returned, and the channel to stay open.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'mandatory-queue-8264';
+
+const returned = [];
+channel.on('return', (msg) => returned.push(msg));
+
+channel.publish('amqp.direct', 'no-route-5103', Buffer.from('dropped'));
+channel.publish('amqp.direct', 'no-route-5103', Buffer.from('returned'), {
+  mandatory: true,
+});
+
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+assert(returned.length === 1, `expected one returned message, got ${returned.length}`);
+
+const [msg] = returned;
+assert(msg.content.toString() === 'returned', 'returned the wrong message');
+assert(msg.fields.replyCode === 312, `wrong reply code ${msg.fields.replyCode}`);
+assert(msg.fields.routingKey === 'no-route-5103', 'wrong routing key');
+
+// the channel can still be used after an unroutable message
+await channel.assertQueue(QUEUE);
+channel.publish('', QUEUE, Buffer.from('routed'), { mandatory: true });
+
+// the message is put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const routed = await channel.get(QUEUE, { noAck: true });
+assert(routed && routed.content.toString() === 'routed', 'did not receive the message');
+assert(returned.length === 1, 'returned a routed message');
+
+await channel.close();
+await connection.close();
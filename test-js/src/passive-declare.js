@@ -0,0 +1,41 @@
+/*
+This test passively declares an existing queue and exchange, and a queue that doesn't exist.
+It expects the existing ones to be found, and the missing queue to not be created.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'passive-queue-4820';
+const MISSING_QUEUE = 'passive-missing-queue-9951';
+
+await channel.assertQueue(QUEUE);
+
+channel.sendToQueue(QUEUE, Buffer.from('STOP'));
+
+// the message is put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const { messageCount } = await channel.checkQueue(QUEUE);
+assert(messageCount === 1, `wrong message count: ${messageCount}`);
+
+await channel.checkExchange('amqp.fanout');
+
+let failed = false;
+await channel.checkQueue(MISSING_QUEUE).catch(() => {
+  failed = true;
+});
+assert(failed, 'found a queue that does not exist');
+
+// the channel was closed by the failed declare
+const newChannel = await connection.createChannel();
+
+failed = false;
+await newChannel.checkQueue(MISSING_QUEUE).catch(() => {
+  failed = true;
+});
+assert(failed, 'passive declare created a queue');
+
+await connection.close();
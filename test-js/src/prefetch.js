@@ -0,0 +1,50 @@
+/*
+This test sets a prefetch limit of one and expects the second message to only arrive
+after the first one has been acknowledged.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'prefetch-queue-5721';
+
+await channel.assertQueue(QUEUE);
+await channel.prefetch(1);
+
+const received = [];
+let onMessage = () => {};
+
+await channel.consume(QUEUE, (msg) => {
+  console.log(`Received '${msg.content.toString()}'`);
+  received.push(msg);
+  onMessage();
+});
+
+const nextMessage = () =>
+  new Promise((resolve) => {
+    onMessage = resolve;
+  });
+
+let next = nextMessage();
+channel.sendToQueue(QUEUE, Buffer.from('first'));
+channel.sendToQueue(QUEUE, Buffer.from('second'));
+await next;
+
+// give the broker the chance to wrongly deliver the second message
+await new Promise((resolve) => setTimeout(resolve, 200));
+assert(received.length === 1, 'second message was delivered before the ack');
+
+next = nextMessage();
+channel.ack(received[0]);
+await next;
+
+assert(
+  received[1].content.toString() === 'second',
+  'second message was not delivered after the ack'
+);
+channel.ack(received[1]);
+
+await channel.close();
+await connection.close();
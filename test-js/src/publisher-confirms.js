@@ -0,0 +1,43 @@
+/*
+This test puts a channel into confirm mode and publishes a few messages to a queue, and one that
+can't be routed anywhere.
+It expects every message to be confirmed, and the routed messages to be in the queue.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createConfirmChannel();
+
+const QUEUE = 'confirm-queue-5572';
+const MESSAGES = 20;
+
+await channel.assertQueue(QUEUE);
+
+const confirmed = [];
+for (let i = 0; i < MESSAGES; i++) {
+  confirmed.push(
+    new Promise((resolve, reject) =>
+      channel.publish('', QUEUE, Buffer.from(`${i}`), {}, (err) =>
+        err ? reject(err) : resolve()
+      )
+    )
+  );
+}
+
+await Promise.all(confirmed);
+
+await new Promise((resolve, reject) =>
+  channel.publish('amqp.direct', 'no-route-2208', Buffer.from('unrouted'), {}, (err) =>
+    err ? reject(err) : resolve()
+  )
+);
+
+// the messages are in the queue once they are confirmed
+const { messageCount } = await channel.checkQueue(QUEUE);
+assert(messageCount === MESSAGES, `expected ${MESSAGES} messages, got ${messageCount}`);
+
+await channel.deleteQueue(QUEUE);
+
+await channel.close();
+await connection.close();
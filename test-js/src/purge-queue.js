@@ -0,0 +1,28 @@
+/*
+This test sends messages to a queue and purges it.
+It expects the purged message count to be reported, and the queue to be empty afterwards.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'purge-queue-3390';
+
+await channel.assertQueue(QUEUE);
+
+channel.sendToQueue(QUEUE, Buffer.from('first'));
+channel.sendToQueue(QUEUE, Buffer.from('second'));
+
+// the messages are put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const { messageCount } = await channel.purgeQueue(QUEUE);
+assert(messageCount === 2, `wrong purged message count: ${messageCount}`);
+
+const msg = await channel.get(QUEUE);
+assert(msg === false, 'queue still contains messages');
+
+await channel.close();
+await connection.close();
@@ -0,0 +1,29 @@
+/*
+This test declares an exchange and redeclares it with a different type.
+It expects the redeclaration to fail, and the exchange to keep its type.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const EXCHANGE = 'redeclare-exchange-3047';
+
+await channel.assertExchange(EXCHANGE, 'fanout');
+
+// redeclaring with the same properties is fine
+await channel.assertExchange(EXCHANGE, 'fanout');
+
+let failed = false;
+await channel.assertExchange(EXCHANGE, 'topic').catch(() => {
+  failed = true;
+});
+assert(failed, 'redeclared an exchange with a different type');
+
+// the channel was closed by the failed declare
+const newChannel = await connection.createChannel();
+await newChannel.assertExchange(EXCHANGE, 'fanout');
+
+await newChannel.close();
+await connection.close();
@@ -0,0 +1,34 @@
+/*
+This test receives a message without acknowledging it and closes the connection.
+It expects the message to be redelivered to a consumer on a second connection.
+ */
+
+import { assert, connectAmqp, waitForMessage } from './utils/utils.js';
+
+const QUEUE = 'redeliver-queue-6203';
+
+const firstConnection = await connectAmqp();
+const firstChannel = await firstConnection.createChannel();
+
+await firstChannel.assertQueue(QUEUE);
+
+const firstConsumer = waitForMessage(firstChannel, QUEUE, 'STOP');
+await firstChannel.sendToQueue(QUEUE, Buffer.from('STOP'));
+await firstConsumer;
+
+// close the connection without acknowledging the message
+await firstConnection.close();
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const msg = await new Promise((resolve) => {
+  channel.consume(QUEUE, resolve);
+});
+
+assert(msg.content.toString() === 'STOP', 'received the wrong message');
+assert(msg.fields.redelivered, 'message is not marked as redelivered');
+channel.ack(msg);
+
+await channel.close();
+await connection.close();
@@ -0,0 +1,46 @@
+/*
+This test starts two consumers on the same queue and sends four messages to it.
+It expects the messages to be distributed evenly between the consumers.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'round-robin-queue-9012';
+const MESSAGES = 4;
+
+await channel.assertQueue(QUEUE);
+
+const counts = [0, 0];
+let total = 0;
+
+const allReceived = new Promise((resolve) => {
+  const consume = (index) =>
+    channel.consume(
+      QUEUE,
+      () => {
+        counts[index] += 1;
+        total += 1;
+        if (total === MESSAGES) {
+          resolve();
+        }
+      },
+      { noAck: true }
+    );
+
+  Promise.all([consume(0), consume(1)]).then(() => {
+    for (let i = 0; i < MESSAGES; i++) {
+      channel.sendToQueue(QUEUE, Buffer.from(`message ${i}`));
+    }
+  });
+});
+
+await allReceived;
+
+console.log(`Consumers received ${counts[0]} and ${counts[1]} messages`);
+assert(counts[0] === 2 && counts[1] === 2, 'messages were not distributed evenly');
+
+await channel.close();
+await connection.close();
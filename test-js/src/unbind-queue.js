@@ -0,0 +1,33 @@
+/*
+This test binds a queue to a topic exchange with two patterns and removes one of the bindings.
+It expects only messages matching the remaining binding to arrive in the queue.
+ */
+
+import { assert, connectAmqp } from './utils/utils.js';
+
+const connection = await connectAmqp();
+const channel = await connection.createChannel();
+
+const QUEUE = 'unbind-queue-7613';
+const EXCHANGE = 'amqp.topic';
+
+await channel.assertQueue(QUEUE);
+await channel.bindQueue(QUEUE, EXCHANGE, 'unbind.removed');
+await channel.bindQueue(QUEUE, EXCHANGE, 'unbind.kept');
+
+await channel.unbindQueue(QUEUE, EXCHANGE, 'unbind.removed');
+
+channel.publish(EXCHANGE, 'unbind.removed', Buffer.from('removed'));
+channel.publish(EXCHANGE, 'unbind.kept', Buffer.from('kept'));
+
+// the messages are put into the queue asynchronously
+await new Promise((resolve) => setTimeout(resolve, 100));
+
+const msg = await channel.get(QUEUE, { noAck: true });
+assert(msg && msg.content.toString() === 'kept', 'did not receive the message');
+
+const empty = await channel.get(QUEUE);
+assert(empty === false, 'received a message from a removed binding');
+
+await channel.close();
+await connection.close();
@@ -38,7 +38,7 @@ fn run_js(test_js_root: &Path) -> Result<()> {
     println!("$ yarn test");
     let status = Command::new("yarn")
         .arg("test")
-        .current_dir(&test_js_root)
+        .current_dir(test_js_root)
         .status()
         .context("yarn test tests")?;
     ensure!(status.success(), "yarn tests failed");